@@ -32,15 +32,13 @@ pub async fn main() {
     let positions: Vec<(usize, usize)> = x.into_iter().zip(y).collect();
     let grid: Grid = Grid::load(&cli.grid).expect("Failed to load the grid");
 
-    let planner = planners::RayCasting {
-        len: cli.size,
-        rays: 8,
-    };
+    let planner = planners::RayCasting::new(cli.size, 8);
     let simulator = simulators::Incremental {
         start_grid: grid.clone(),
         increment_step: 1,
     };
-    let paths = match run::run_drone_simulation(cli, planner, simulator, positions.clone(), &grid) {
+    let results = match run::run_drone_simulation(cli, planner, simulator, positions.clone(), &grid)
+    {
         Ok(result_path) => result_path,
         Err(_) => {
             log::error!("Failed the simulation");
@@ -50,7 +48,7 @@ pub async fn main() {
 
     // INFO: End
     // Aggregate the results and format them for printing
-    let path_traces = create_path_traces(&paths, &positions, &grid);
+    let path_traces = create_path_traces(&results.paths, &results.rewards);
 
     if !path_traces.is_empty() {
         plot_paths(&grid, &path_traces);
@@ -0,0 +1,94 @@
+/// The battery and charging model shared by the energy aware planners and simulators.
+#[derive(Clone, Debug)]
+pub struct EnergyModel {
+    /// The charge of a full battery.
+    pub capacity: f32,
+    /// The charge used by a horizontal or vertical move.
+    pub straight_cost: f32,
+    /// The charge used by a diagonal move.
+    pub diagonal_cost: f32,
+    /// The charge regained per step spent on a charging station.
+    pub charge_rate: f32,
+    /// The positions of the charging stations.
+    pub stations: Vec<(usize, usize)>,
+}
+
+impl EnergyModel {
+    /// Returns the charge needed to travel between two positions on the cheapest 8-connected path.
+    pub fn travel_cost(&self, from: (usize, usize), to: (usize, usize)) -> f32 {
+        let dx = from.0.abs_diff(to.0);
+        let dy = from.1.abs_diff(to.1);
+        let diagonal = dx.min(dy);
+        let straight = dx.max(dy) - diagonal;
+
+        // Two straight moves are cheaper than a diagonal one if diagonals are expensive
+        let diagonal_cost = self.diagonal_cost.min(2. * self.straight_cost);
+        diagonal as f32 * diagonal_cost + straight as f32 * self.straight_cost
+    }
+
+    /// Returns the charge used by a single move between two neighbouring positions.
+    ///
+    /// Moves over a larger distance are charged as the cheapest path between the positions.
+    pub fn move_cost(&self, from: (usize, usize), to: (usize, usize)) -> f32 {
+        match (from.0.abs_diff(to.0), from.1.abs_diff(to.1)) {
+            (0, 0) => 0.,
+            (1, 1) => self.diagonal_cost,
+            _ => self.travel_cost(from, to),
+        }
+    }
+
+    /// Returns the charge used to move along consecutive positions.
+    pub fn path_cost<'a>(&self, steps: impl IntoIterator<Item = &'a (usize, usize)>) -> f32 {
+        let mut steps = steps.into_iter();
+        let Some(mut previous) = steps.next().copied() else {
+            return 0.;
+        };
+        steps
+            .map(|&step| {
+                let cost = self.move_cost(previous, step);
+                previous = step;
+                cost
+            })
+            .sum()
+    }
+
+    /// Returns the nearest charging station and the charge needed to reach it.
+    pub fn nearest_station(&self, from: (usize, usize)) -> Option<((usize, usize), f32)> {
+        self.stations
+            .iter()
+            .map(|&station| (station, self.travel_cost(from, station)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Returns the charge needed to reach the nearest charging station.
+    pub fn return_cost(&self, from: (usize, usize)) -> f32 {
+        self.nearest_station(from).map_or(0., |(_, cost)| cost)
+    }
+
+    /// Returns whether the given position is a charging station.
+    pub fn is_station(&self, position: (usize, usize)) -> bool {
+        self.stations.contains(&position)
+    }
+
+    /// Returns the cells of the cheapest 8-connected path between two positions, both included.
+    pub fn route(&self, from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+        let use_diagonals = self.diagonal_cost <= 2. * self.straight_cost;
+        let mut route = vec![from];
+        let mut current = from;
+
+        while current != to {
+            let step_x = current.0 != to.0;
+            let step_y = current.1 != to.1;
+            let toward = |from: usize, to: usize| if to > from { from + 1 } else { from - 1 };
+
+            current = match (step_x, step_y) {
+                (true, true) if use_diagonals => (toward(current.0, to.0), toward(current.1, to.1)),
+                (true, _) => (toward(current.0, to.0), current.1),
+                _ => (current.0, toward(current.1, to.1)),
+            };
+            route.push(current);
+        }
+
+        route
+    }
+}
@@ -1,2 +1,3 @@
+pub mod energy;
 pub mod traits;
 pub mod types;
@@ -1,5 +1,8 @@
-use crate::types::{Grid, Path};
+use crate::run::Fleet;
+use crate::types::{DroneState, Grid, Move, Path};
+use crate::utils::Budget;
 use anyhow::Result;
+use std::sync::Arc;
 
 /// A trait for path planning algorithms.
 ///
@@ -17,6 +20,110 @@ pub trait Planner {
     ///
     /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
     fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path>;
+
+    /// Attempts to find a path on the given grid for a drone in the given state.
+    ///
+    /// The default implementation plans from the position of the drone. Planners that depend on
+    /// more of the drone state, such as its remaining charge, override this method.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.solve(grid, drone.position)
+    }
+
+    /// Attempts to find a path for a drone within a time budget.
+    ///
+    /// Anytime planners check the budget while they search and return the best path found so
+    /// far once it expires. The default implementation ignores the budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the call may take.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        let _ = budget;
+        self.plan(grid, drone)
+    }
+
+    /// Settles the decisions a drone shares with the rest of the fleet before anyone plans.
+    ///
+    /// Called for every drone, one at a time in drone id order, before the drones plan, possibly
+    /// in parallel. Planners that coordinate drones through shared state, such as claimed
+    /// targets, update it here so the outcome does not depend on which drone plans first. The
+    /// default implementation does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid the drone is about to plan on.
+    /// * `drone` - The current state of the drone.
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        let _ = (grid, drone);
+    }
+
+    /// Forgets the state kept for a drone that left the fleet, such as its claimed targets, so
+    /// they are free for the other drones. The default implementation does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `drone` - The id of the drone that left.
+    fn release(&self, drone: usize) {
+        let _ = drone;
+    }
+
+    /// Returns whether the planner keeps state between steps, such as claimed targets or planned
+    /// tours, which checkpoints do not record. The default implementation returns `false`.
+    fn keeps_state(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the paths planned depend on the time the planner is given, such as for
+    /// searches bounded by a time budget, so a run cannot be reproduced from a checkpoint. The
+    /// default implementation returns `false`.
+    fn depends_on_time(&self) -> bool {
+        false
+    }
+}
+
+impl<P: Planner + ?Sized> Planner for Box<P> {
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        (**self).solve(grid, start)
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        (**self).plan(grid, drone)
+    }
+
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        (**self).plan_within(grid, drone, budget)
+    }
+
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        (**self).assign(grid, drone)
+    }
+
+    fn release(&self, drone: usize) {
+        (**self).release(drone)
+    }
+
+    fn keeps_state(&self) -> bool {
+        (**self).keeps_state()
+    }
+
+    fn depends_on_time(&self) -> bool {
+        (**self).depends_on_time()
+    }
 }
 
 /// A trait for simulation algorithms.
@@ -37,4 +144,133 @@ pub trait Simulator {
     /// - On success: A tuple with the updated grid and the final position (x, y).
     /// - On failure: An error describing what went wrong.
     fn solve(&self, grid: &Grid, path: &Path) -> Result<(Grid, (usize, usize))>;
+
+    /// Simulates the execution of a path by a drone, updating the state of the drone.
+    ///
+    /// The default implementation moves the drone to the position returned by `solve` and
+    /// reports the value the grid lost as the harvest of the drone.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The initial state of the grid.
+    /// * `drone` - The state of the drone executing the path.
+    /// * `path` - The path to simulate.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the updated grid, or an error describing what went wrong.
+    fn advance(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        let (new_grid, new_position) = self.solve(grid, path)?;
+        drone.position = new_position;
+        drone.harvested = (grid.data.iter().zip(&new_grid.data))
+            .map(|(&before, &after)| before.saturating_sub(after) as usize)
+            .sum();
+        Ok(new_grid)
+    }
+
+    /// Simulates the execution of a path by a drone like `advance`, without the grid recovering.
+    ///
+    /// Runtimes applying several paths within one step harvest every path and then let the grid
+    /// recover once with `regrow`. The default implementation calls `advance`, which suits
+    /// simulators whose grid does not recover.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The initial state of the grid.
+    /// * `drone` - The state of the drone executing the path.
+    /// * `path` - The path to simulate.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the updated grid, or an error describing what went wrong.
+    fn harvest(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        self.advance(grid, drone, path)
+    }
+
+    /// Lets the grid recover for one step. The default implementation does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid to recover.
+    fn regrow(&self, grid: &mut Grid) {
+        let _ = grid;
+    }
+}
+
+impl<S: Simulator + ?Sized> Simulator for Box<S> {
+    fn solve(&self, grid: &Grid, path: &Path) -> Result<(Grid, (usize, usize))> {
+        (**self).solve(grid, path)
+    }
+
+    fn advance(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        (**self).advance(grid, drone, path)
+    }
+
+    fn harvest(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        (**self).harvest(grid, drone, path)
+    }
+
+    fn regrow(&self, grid: &mut Grid) {
+        (**self).regrow(grid)
+    }
+}
+
+impl<S: Simulator + ?Sized> Simulator for Arc<S> {
+    fn solve(&self, grid: &Grid, path: &Path) -> Result<(Grid, (usize, usize))> {
+        (**self).solve(grid, path)
+    }
+
+    fn advance(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        (**self).advance(grid, drone, path)
+    }
+
+    fn harvest(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        (**self).harvest(grid, drone, path)
+    }
+
+    fn regrow(&self, grid: &mut Grid) {
+        (**self).regrow(grid)
+    }
+}
+
+/// A trait for observers of a simulation run.
+///
+/// Observers are told about every move and every finished step while the simulation runs, so
+/// they can export or display a run without it being held in memory. All methods do nothing by
+/// default.
+pub trait Observer {
+    /// Called after a drone moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The move of the drone.
+    ///
+    /// # Returns
+    ///
+    /// A Result that is an error if the observer failed, which ends the run.
+    fn on_move(&mut self, event: &Move) -> Result<()> {
+        let _ = event;
+        Ok(())
+    }
+
+    /// Called at the end of every step.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The step that ended.
+    /// * `grid` - The grid at the end of the step.
+    /// * `fleet` - The drones with their paths so far.
+    ///
+    /// # Returns
+    ///
+    /// A Result that is an error if the observer failed, which ends the run.
+    fn on_step(&mut self, step: usize, grid: &Grid, fleet: &Fleet) -> Result<()> {
+        let _ = (step, grid, fleet);
+        Ok(())
+    }
+
+    /// Called once the run is over.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
@@ -1,11 +1,13 @@
 use crate::config::grids::*;
+use crate::sensing::BeliefMap;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
 /// Represents a path with steps and total cost.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Path {
     /// The sequence of steps in the path, represented as (x, y) coordinates.
     pub steps: VecDeque<(usize, usize)>,
@@ -23,6 +25,102 @@ impl fmt::Display for Path {
     }
 }
 
+/// Represents the area a drone harvests around its position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Footprint {
+    /// The radius of the square harvested around the drone, 0 for a single cell.
+    pub radius: usize,
+    /// The value harvested from every cell in the footprint.
+    pub amount: u8,
+}
+
+impl Default for Footprint {
+    /// Harvests everything from the cell the drone is on.
+    fn default() -> Self {
+        Footprint {
+            radius: 0,
+            amount: u8::MAX,
+        }
+    }
+}
+
+/// Represents a rectangular area drones cannot fly over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Obstacle {
+    /// The row of the top left corner.
+    pub x: usize,
+    /// The column of the top left corner.
+    pub y: usize,
+    /// The number of rows covered.
+    pub height: usize,
+    /// The number of columns covered.
+    pub width: usize,
+}
+
+impl Obstacle {
+    /// Returns whether the obstacle covers the cell.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.height).contains(&x) && (self.y..self.y + self.width).contains(&y)
+    }
+}
+
+/// Represents the state of a single drone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DroneState {
+    /// The id of the drone within its fleet.
+    pub id: usize,
+    /// The current position of the drone, as (x, y) coordinates.
+    pub position: (usize, usize),
+    /// The remaining battery charge, `None` if the drone is not energy constrained.
+    pub charge: Option<f32>,
+    /// The index of the profile of the drone, selecting its planner.
+    pub profile: usize,
+    /// The number of cells the drone moves per step.
+    pub speed: usize,
+    /// The area the drone harvests on every cell it passes.
+    pub footprint: Footprint,
+    /// The knowledge of the drone, `None` if it sees the whole grid.
+    pub belief: Option<BeliefMap>,
+    /// The value the drone harvested in its last step, as reported by the simulator.
+    pub harvested: usize,
+}
+
+impl DroneState {
+    /// Creates the state of a default drone without a battery at the given position.
+    pub fn new(position: (usize, usize)) -> Self {
+        DroneState {
+            id: 0,
+            position,
+            charge: None,
+            profile: 0,
+            speed: 1,
+            footprint: Footprint::default(),
+            belief: None,
+            harvested: 0,
+        }
+    }
+}
+
+/// A move of a single drone during a step, as seen by observers of the run.
+pub struct Move<'a> {
+    /// The step the move was made in.
+    pub step: usize,
+    /// The id of the drone.
+    pub drone: usize,
+    /// The position of the drone before the move.
+    pub from: (usize, usize),
+    /// The position of the drone after the move.
+    pub to: (usize, usize),
+    /// The path the drone planned.
+    pub path: &'a Path,
+    /// The value the drone harvested in the move, as reported by the simulator.
+    pub reward: usize,
+    /// The grid before the move.
+    pub before: &'a Grid,
+    /// The grid after the move.
+    pub after: &'a Grid,
+}
+
 /// Represents a node in a grid.
 #[derive(Clone)]
 pub struct Node {
@@ -51,7 +149,7 @@ impl Hash for Node {
 impl Eq for Node {}
 
 /// Represents a grid of values.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Grid {
     /// The raw data of the grid, stored as a vector of u8.
     pub data: Vec<u8>,
@@ -72,14 +170,28 @@ impl Grid {
     }
 
     /// Subtracts a value from a range of cells around a specified point, using saturation arithmetic.
-    pub fn saturated_subtract_at(&mut self, row: usize, col: usize, range: usize, amount: usize) {
+    ///
+    /// # Returns
+    ///
+    /// The total value removed from the cells.
+    pub fn saturated_subtract_at(
+        &mut self,
+        row: usize,
+        col: usize,
+        range: usize,
+        amount: usize,
+    ) -> usize {
         let (start_row, end_row, start_col, end_col) = self.get_start_end_row_col(row, col, range);
+        let mut removed = 0;
         for r in start_row..end_row {
             for c in start_col..end_col {
                 let index = r * self.size + c;
-                self.data[index] = self.data[index].saturating_sub(amount as u8);
+                let value = self.data[index];
+                self.data[index] = value.saturating_sub(amount as u8);
+                removed += (value - self.data[index]) as usize;
             }
         }
+        removed
     }
 
     /// Adds a value to a range of cells around a specified point, using saturation arithmetic.
@@ -138,7 +250,7 @@ impl Grid {
         for r in start_row..end_row {
             for c in start_col..end_col {
                 let index = r * self.size + c;
-                self.data[index] = self.data[index].max(source_grid.data[index] as u8);
+                self.data[index] = self.data[index].max(source_grid.data[index]);
             }
         }
     }
@@ -0,0 +1,253 @@
+use crate::parsers::Cli;
+use crate::sensing::Peer;
+use crate::types::DroneState;
+use crate::utils::Rng;
+use std::collections::HashSet;
+
+/// A limited range radio network between drones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    /// The largest distance, in cells along either axis, over which two drones can talk.
+    pub range: usize,
+    /// Whether messages are relayed along chains of drones in range of each other.
+    pub relay: bool,
+    /// The maximum number of cells a drone can receive per step, if limited.
+    pub bandwidth: Option<usize>,
+    /// The probability that a link between two drones drops for a step.
+    pub loss: f32,
+}
+
+impl Network {
+    /// Creates the network from the command line arguments, if communication is limited.
+    pub fn from_cli(cli: &Cli) -> Option<Self> {
+        cli.comm_range.map(|range| Network {
+            range,
+            relay: cli.relay,
+            bandwidth: cli.bandwidth,
+            loss: cli.comm_loss,
+        })
+    }
+
+    /// Returns, for every drone, the other drones it can reach this step.
+    ///
+    /// # Arguments
+    ///
+    /// * `positions` - The positions of the drones.
+    /// * `rng` - The random number generator deciding which links drop.
+    ///
+    /// # Returns
+    ///
+    /// The indices of the reachable drones for every drone, in ascending order.
+    pub fn reachable(&self, positions: &[(usize, usize)], rng: &mut Rng) -> Vec<Vec<usize>> {
+        let n = positions.len();
+        let mut links = vec![Vec::new(); n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (a, b) = (positions[i], positions[j]);
+                let distance = a.0.abs_diff(b.0).max(a.1.abs_diff(b.1));
+                if distance <= self.range && rng.next_f32() >= self.loss {
+                    links[i].push(j);
+                    links[j].push(i);
+                }
+            }
+        }
+
+        if !self.relay {
+            return links;
+        }
+
+        // Everyone in the same connected component can be reached through relays
+        let mut component = vec![usize::MAX; n];
+        for start in 0..n {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            component[start] = start;
+            let mut stack = vec![start];
+            while let Some(drone) = stack.pop() {
+                for &other in &links[drone] {
+                    if component[other] == usize::MAX {
+                        component[other] = start;
+                        stack.push(other);
+                    }
+                }
+            }
+        }
+
+        (0..n)
+            .map(|i| {
+                (0..n)
+                    .filter(|&j| j != i && component[j] == component[i])
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Exchanges positions, plans and belief maps between drones that can reach each other.
+    ///
+    /// Every drone learns the position and last plan of the drones it reaches. It then receives
+    /// the cells they know more recent values of, newest observations first, up to the bandwidth.
+    /// Drones without a belief map neither send nor receive.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The ids of the drones.
+    /// * `drones` - The states of the drones, updated in place.
+    /// * `plans` - The last planned cells of every drone.
+    /// * `rng` - The random number generator deciding which links drop.
+    pub fn exchange(
+        &self,
+        ids: &[usize],
+        drones: &mut [&mut DroneState],
+        plans: &[Vec<(usize, usize)>],
+        rng: &mut Rng,
+    ) {
+        let positions: Vec<(usize, usize)> = drones.iter().map(|drone| drone.position).collect();
+        let reachable = self.reachable(&positions, rng);
+
+        // Send what was known before this exchange, so nothing is forwarded twice in one step
+        let beliefs: Vec<_> = drones.iter().map(|drone| drone.belief.clone()).collect();
+
+        for (receiver, drone) in drones.iter_mut().enumerate() {
+            let Some(belief) = drone.belief.as_mut() else {
+                continue;
+            };
+
+            belief.peers = Some(
+                reachable[receiver]
+                    .iter()
+                    .map(|&sender| Peer {
+                        drone: ids[sender],
+                        position: positions[sender],
+                        plan: plans[sender].clone(),
+                    })
+                    .collect(),
+            );
+
+            let Some(mine) = &beliefs[receiver] else {
+                continue;
+            };
+            let mut cells: Vec<(usize, u8, usize)> = reachable[receiver]
+                .iter()
+                .filter_map(|&sender| beliefs[sender].as_ref())
+                .flat_map(|theirs| theirs.newer_than(mine))
+                .collect();
+
+            // Newest first, ties broken by cell so the result does not depend on sender order.
+            // Only the newest observation of a cell is kept, whichever sender it came from.
+            cells.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+            let mut received = HashSet::new();
+            cells.retain(|cell| received.insert(cell.0));
+            if let Some(bandwidth) = self.bandwidth {
+                cells.truncate(bandwidth);
+            }
+
+            for (index, value, seen_at) in cells {
+                belief.merge(index, value, seen_at);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensing::BeliefMap;
+
+    /// Tests that maps only travel between drones in range, through relays and within bandwidth.
+    #[test]
+    fn test_exchange() {
+        let mut network = Network {
+            range: 3,
+            relay: false,
+            bandwidth: None,
+            loss: 0.,
+        };
+        let mut rng = Rng::new(0);
+
+        let mut states: Vec<DroneState> = [(0, 0), (3, 3), (6, 6)]
+            .into_iter()
+            .map(|position| DroneState {
+                belief: Some(BeliefMap::new(10, 0)),
+                ..DroneState::new(position)
+            })
+            .collect();
+        for (step, state) in states.iter_mut().enumerate() {
+            let (x, y) = state.position;
+            state.belief.as_mut().unwrap().record(x, y, 20, step);
+        }
+        let plans = vec![Vec::new(); 3];
+
+        assert_eq!(
+            network.reachable(&[(0, 0), (3, 3), (6, 6)], &mut rng),
+            vec![vec![1], vec![0, 2], vec![1]]
+        );
+
+        let mut direct = states.clone();
+        network.exchange(
+            &[0, 1, 2],
+            &mut direct.iter_mut().collect::<Vec<_>>(),
+            &plans,
+            &mut rng,
+        );
+        let first = direct[0].belief.as_ref().unwrap();
+        assert!(first.is_known(3, 3));
+        assert!(!first.is_known(6, 6), "Drone 2 is out of range of drone 0");
+        assert_eq!(first.peers.as_ref().unwrap().len(), 1);
+
+        network.relay = true;
+        network.bandwidth = Some(1);
+        let mut relayed = states.clone();
+        network.exchange(
+            &[0, 1, 2],
+            &mut relayed.iter_mut().collect::<Vec<_>>(),
+            &plans,
+            &mut rng,
+        );
+        let first = relayed[0].belief.as_ref().unwrap();
+        assert!(
+            first.is_known(6, 6),
+            "Newest cell arrives first through the relay"
+        );
+        assert!(!first.is_known(3, 3), "Bandwidth allows a single cell");
+    }
+
+    /// Tests that a cell heard from two senders takes up the bandwidth only once, newest first.
+    #[test]
+    fn test_exchange_keeps_newest_of_duplicate_cells() {
+        let network = Network {
+            range: 3,
+            relay: false,
+            bandwidth: Some(3),
+            loss: 0.,
+        };
+        let mut rng = Rng::new(0);
+
+        let mut states: Vec<DroneState> = [(0, 0), (1, 1), (2, 2)]
+            .into_iter()
+            .map(|position| DroneState {
+                belief: Some(BeliefMap::new(10, 0)),
+                ..DroneState::new(position)
+            })
+            .collect();
+        states[1].belief.as_mut().unwrap().record(5, 5, 30, 3);
+        let second = states[2].belief.as_mut().unwrap();
+        second.record(6, 6, 20, 2);
+        second.record(5, 5, 10, 1);
+        second.record(7, 7, 5, 0);
+
+        network.exchange(
+            &[0, 1, 2],
+            &mut states.iter_mut().collect::<Vec<_>>(),
+            &[Vec::new(), Vec::new(), Vec::new()],
+            &mut rng,
+        );
+        let first = states[0].belief.as_ref().unwrap();
+        assert_eq!(first.seen_at(5, 5), Some(3));
+        assert_eq!(first.seen_at(6, 6), Some(2));
+        assert!(
+            first.is_known(7, 7),
+            "The older copy of (5, 5) must not use up the bandwidth"
+        );
+    }
+}
@@ -1,2 +1,4 @@
 pub mod grids;
+pub mod scenario;
 pub use grids::{GRID_L, GRID_M, GRID_S, GRID_TEST_LINE, GRID_TEST_SINGLE};
+pub use scenario::{DroneProfile, PlannerConfig, Scenario, SharedPlanning};
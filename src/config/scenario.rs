@@ -0,0 +1,459 @@
+use crate::parsers::Cli;
+use crate::parsers::PlannerKind;
+use crate::planners::{
+    Annealing, Claims, Frontier, Genetic, Orienteering, Profiled, RayCasting, Tours,
+};
+use crate::traits::{Planner, Simulator};
+use crate::types::{DroneState, Footprint};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The planner of a drone profile.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannerConfig {
+    /// The ray casting planner.
+    RayCasting {
+        /// The length of each ray.
+        len: usize,
+        /// The number of rays to cast.
+        rays: usize,
+        /// The number of empty cells added to every ray when averaging its value.
+        #[serde(default = "edge_bias")]
+        edge_bias: f32,
+        /// The weight of every cell of a ray relative to the previous one.
+        #[serde(default = "falloff")]
+        falloff: f32,
+    },
+    /// The frontier exploration planner, handing over to a fallback once the grid is explored.
+    Frontier {
+        /// The minimal distance between frontiers of different drones.
+        separation: usize,
+        /// The planner used once no frontiers are left.
+        fallback: Box<PlannerConfig>,
+    },
+    /// The orienteering planner, planning a whole tour once.
+    Orienteering {
+        /// The maximum number of moves of the tour.
+        budget: usize,
+        /// The cell the tour has to end at, if any.
+        #[serde(default)]
+        end: Option<(usize, usize)>,
+        /// The number of local search iterations.
+        iterations: usize,
+        /// The seed of the randomised search.
+        #[serde(default)]
+        seed: u64,
+    },
+    /// The genetic algorithm over sequences of moves.
+    Genetic {
+        /// The number of moves of a sequence.
+        horizon: usize,
+        /// The number of sequences of a generation.
+        population: usize,
+        /// The maximum number of generations.
+        generations: usize,
+        /// The probability that a move of a child is replaced by a random one.
+        mutation_rate: f32,
+        /// The time budget of a single call in ms.
+        budget: usize,
+        /// The seed of the search.
+        #[serde(default)]
+        seed: u64,
+    },
+    /// Simulated annealing over sequences of moves.
+    Annealing {
+        /// The number of moves of a sequence.
+        horizon: usize,
+        /// The maximum number of iterations.
+        iterations: usize,
+        /// The starting temperature.
+        temperature: f32,
+        /// The time budget of a single call in ms.
+        budget: usize,
+        /// The seed of the search.
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        PlannerConfig::RayCasting {
+            len: 5,
+            rays: 16,
+            edge_bias: RayCasting::EDGE_BIAS,
+            falloff: RayCasting::FALLOFF,
+        }
+    }
+}
+
+/// Returns the default edge bias of the ray casting planner, for scenario files without one.
+fn edge_bias() -> f32 {
+    RayCasting::EDGE_BIAS
+}
+
+/// Returns the default falloff of the ray casting planner, for scenario files without one.
+fn falloff() -> f32 {
+    RayCasting::FALLOFF
+}
+
+impl PlannerConfig {
+    /// Builds the configured planner.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared` - The state shared by the planners of the fleet.
+    pub fn build(&self, shared: &SharedPlanning) -> Box<dyn Planner + Send + Sync> {
+        match self {
+            &PlannerConfig::RayCasting {
+                len,
+                rays,
+                edge_bias,
+                falloff,
+            } => Box::new(RayCasting {
+                falloff,
+                ..RayCasting::new(len, rays).with_edge_bias(edge_bias)
+            }),
+            PlannerConfig::Frontier {
+                separation,
+                fallback,
+            } => Box::new(Frontier {
+                inner: fallback.build(shared),
+                separation: *separation,
+                claims: Arc::clone(&shared.claims),
+            }),
+            &PlannerConfig::Orienteering {
+                budget,
+                end,
+                iterations,
+                seed,
+            } => Box::new(Orienteering {
+                tours: Arc::clone(&shared.tours),
+                ..Orienteering::new(budget, end, iterations, seed)
+            }),
+            &PlannerConfig::Genetic {
+                horizon,
+                population,
+                generations,
+                mutation_rate,
+                budget,
+                seed,
+            } => Box::new(Genetic {
+                simulator: Arc::clone(&shared.model),
+                horizon,
+                population,
+                generations,
+                mutation_rate,
+                budget,
+                seed,
+            }),
+            &PlannerConfig::Annealing {
+                horizon,
+                iterations,
+                temperature,
+                budget,
+                seed,
+            } => Box::new(Annealing {
+                simulator: Arc::clone(&shared.model),
+                horizon,
+                iterations,
+                temperature,
+                budget,
+                seed,
+            }),
+        }
+    }
+}
+
+/// The state shared by the planners of all drones of a fleet.
+pub struct SharedPlanning {
+    /// The simulator planners predict the grid with.
+    pub model: Arc<dyn Simulator + Send + Sync>,
+    /// The frontiers claimed by frontier planners.
+    pub claims: Arc<Claims>,
+    /// The tours planned by orienteering planners.
+    pub tours: Arc<Tours>,
+}
+
+/// The planner and physical characteristics shared by a class of drones.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DroneProfile {
+    /// The name drones refer to the profile by.
+    pub name: String,
+    /// The planner of the drones.
+    pub planner: PlannerConfig,
+    /// The number of cells the drones move per step.
+    pub speed: usize,
+    /// The area the drones harvest on every cell they pass.
+    pub footprint: Footprint,
+}
+
+impl Default for DroneProfile {
+    fn default() -> Self {
+        DroneProfile {
+            name: String::from("default"),
+            planner: PlannerConfig::default(),
+            speed: 1,
+            footprint: Footprint::default(),
+        }
+    }
+}
+
+/// A drone of a scenario file.
+#[derive(Serialize, Deserialize)]
+struct ScenarioDrone {
+    /// The starting position of the drone.
+    position: (usize, usize),
+    /// The name of the profile of the drone, the first profile if omitted.
+    profile: Option<String>,
+}
+
+/// The layout of a scenario file.
+#[derive(Serialize, Deserialize)]
+struct ScenarioFile {
+    #[serde(default)]
+    profiles: Vec<DroneProfile>,
+    drones: Vec<ScenarioDrone>,
+}
+
+/// The drones of a simulation and the profiles they are built from.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    /// The available profiles, drones refer to them by index.
+    pub profiles: Vec<DroneProfile>,
+    /// The starting state of every drone.
+    pub drones: Vec<DroneState>,
+}
+
+impl Scenario {
+    /// Loads a scenario from a JSON file.
+    ///
+    /// ```json
+    /// {
+    ///   "profiles": [
+    ///     { "name": "scout", "planner": { "ray_casting": { "len": 12, "rays": 8 } }, "speed": 2,
+    ///       "footprint": { "radius": 0, "amount": 10 } },
+    ///     { "name": "harvester", "footprint": { "radius": 1, "amount": 255 } },
+    ///     { "name": "surveyor", "planner": { "frontier": { "separation": 3,
+    ///       "fallback": { "ray_casting": { "len": 8, "rays": 16 } } } } }
+    ///   ],
+    ///   "drones": [
+    ///     { "position": [3, 7], "profile": "scout" },
+    ///     { "position": [12, 3], "profile": "harvester" }
+    ///   ]
+    /// }
+    /// ```
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario: {}", path))?;
+        Self::parse(&text)
+    }
+
+    /// Parses a scenario from its JSON representation.
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: ScenarioFile = serde_json::from_str(text)?;
+        let mut profiles = file.profiles;
+        if profiles.is_empty() {
+            profiles.push(DroneProfile::default());
+        }
+
+        let drones = file
+            .drones
+            .into_iter()
+            .map(|drone| {
+                let profile = match &drone.profile {
+                    Some(name) => profiles
+                        .iter()
+                        .position(|profile| &profile.name == name)
+                        .ok_or_else(|| anyhow::anyhow!("Unknown profile: {}", name))?,
+                    None => 0,
+                };
+                Ok(Self::drone(&profiles, profile, drone.position))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Scenario { profiles, drones })
+    }
+
+    /// Serialises the scenario as a scenario file, see [`Scenario::load`].
+    pub fn to_json(&self) -> Result<String> {
+        let file = ScenarioFile {
+            profiles: self.profiles.clone(),
+            drones: self
+                .drones
+                .iter()
+                .map(|drone| ScenarioDrone {
+                    position: drone.position,
+                    profile: Some(self.profiles[drone.profile].name.clone()),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    /// Saves the scenario as a scenario file.
+    pub fn save(&self, path: &str) -> Result<()> {
+        std::fs::write(path, self.to_json()? + "\n")
+            .with_context(|| format!("Failed to write scenario: {}", path))
+    }
+
+    /// Creates a scenario from the command line arguments.
+    ///
+    /// Every starting drone gets its own profile, taking the per-drone options in the order the
+    /// drones were given. Options that are missing for a drone fall back to their defaults.
+    pub fn from_cli(cli: &Cli) -> Self {
+        let positions: Vec<(usize, usize)> = cli
+            .pos_x
+            .iter()
+            .copied()
+            .zip(cli.pos_y.iter().copied())
+            .collect();
+
+        let profiles: Vec<DroneProfile> = (0..positions.len().max(1))
+            .map(|drone| {
+                let ray_casting = PlannerConfig::RayCasting {
+                    len: cli.ray_len.get(drone).copied().unwrap_or(cli.size),
+                    rays: cli.rays.get(drone).copied().unwrap_or(16),
+                    edge_bias: cli.edge_bias,
+                    falloff: cli.falloff,
+                };
+                let planner = match cli.planner.get(drone) {
+                    Some(PlannerKind::Frontier) => PlannerConfig::Frontier {
+                        separation: cli.frontier_separation,
+                        fallback: Box::new(ray_casting),
+                    },
+                    Some(PlannerKind::Orienteering) => PlannerConfig::Orienteering {
+                        budget: cli.time_steps,
+                        end: cli.tour_end.get(drone).copied(),
+                        iterations: cli.tour_iterations,
+                        seed: cli.seed,
+                    },
+                    Some(PlannerKind::Genetic) => PlannerConfig::Genetic {
+                        horizon: cli.horizon,
+                        population: cli.population,
+                        generations: cli.generations,
+                        mutation_rate: cli.mutation_rate,
+                        budget: cli.plan_budget,
+                        seed: cli.seed,
+                    },
+                    Some(PlannerKind::Annealing) => PlannerConfig::Annealing {
+                        horizon: cli.horizon,
+                        iterations: cli.annealing_iterations,
+                        temperature: cli.temperature,
+                        budget: cli.plan_budget,
+                        seed: cli.seed,
+                    },
+                    Some(PlannerKind::RayCasting) | None => ray_casting,
+                };
+                DroneProfile {
+                    name: format!("drone {}", drone),
+                    planner,
+                    speed: cli.speed.get(drone).copied().unwrap_or(1),
+                    footprint: cli.footprint.get(drone).copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let drones = positions
+            .iter()
+            .enumerate()
+            .map(|(drone, &position)| Self::drone(&profiles, drone, position))
+            .collect();
+
+        Scenario { profiles, drones }
+    }
+
+    /// Loads the scenario file given on the command line, or builds the scenario from the
+    /// command line arguments if there is none.
+    pub fn from_cli_or_file(cli: &Cli) -> Result<Self> {
+        match &cli.scenario {
+            Some(path) => Self::load(path),
+            None => Ok(Self::from_cli(cli)),
+        }
+    }
+
+    /// Returns the starting positions of the drones.
+    pub fn positions(&self) -> Vec<(usize, usize)> {
+        self.drones.iter().map(|drone| drone.position).collect()
+    }
+
+    /// Builds a planner that plans every drone with the planner of its profile.
+    ///
+    /// All frontier planners share their claims and all orienteering planners their tours, so
+    /// drones of different profiles do not head for the same frontier or rewards.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The simulator planners predict the grid with.
+    pub fn planner(&self, model: Arc<dyn Simulator + Send + Sync>) -> Profiled {
+        let shared = SharedPlanning {
+            model,
+            claims: Arc::default(),
+            tours: Arc::default(),
+        };
+        Profiled {
+            planners: self
+                .profiles
+                .iter()
+                .map(|profile| profile.planner.build(&shared))
+                .collect(),
+        }
+    }
+
+    /// Creates the starting state of a drone with the given profile.
+    fn drone(profiles: &[DroneProfile], profile: usize, position: (usize, usize)) -> DroneState {
+        DroneState {
+            profile,
+            speed: profiles[profile].speed.max(1),
+            footprint: profiles[profile].footprint,
+            ..DroneState::new(position)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that drones of a scenario file get the profile they refer to.
+    #[test]
+    fn test_parse_scenario() {
+        let scenario = Scenario::parse(
+            r#"{
+                "profiles": [
+                    { "name": "scout", "planner": { "ray_casting": { "len": 12, "rays": 8 } },
+                      "speed": 2, "footprint": { "radius": 0, "amount": 10 } },
+                    { "name": "harvester", "footprint": { "radius": 1, "amount": 255 } }
+                ],
+                "drones": [
+                    { "position": [3, 7], "profile": "harvester" },
+                    { "position": [12, 3] }
+                ]
+            }"#,
+        )
+        .expect("Scenario should parse");
+
+        assert_eq!(scenario.positions(), vec![(3, 7), (12, 3)]);
+        assert_eq!(scenario.drones[0].profile, 1);
+        assert_eq!(scenario.drones[0].speed, 1);
+        assert_eq!(scenario.drones[0].footprint.radius, 1);
+        assert_eq!(scenario.drones[1].profile, 0);
+        assert_eq!(scenario.drones[1].speed, 2);
+        assert_eq!(
+            scenario.profiles[0].planner,
+            PlannerConfig::RayCasting {
+                len: 12,
+                rays: 8,
+                edge_bias: RayCasting::EDGE_BIAS,
+                falloff: RayCasting::FALLOFF,
+            }
+        );
+
+        assert!(
+            Scenario::parse(r#"{ "drones": [{ "position": [0, 0], "profile": "x" }] }"#).is_err()
+        );
+    }
+}
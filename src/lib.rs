@@ -1,10 +1,13 @@
 pub mod common;
+pub mod comms;
 pub mod config;
 pub mod parsers;
 pub mod planners;
 pub mod run;
+pub mod sensing;
 pub mod simulators;
 pub mod utils;
 
+pub use common::energy;
 pub use common::traits;
 pub use common::types;
@@ -1,14 +1,20 @@
-use planner::parsers::Cli;
+use planner::config::Scenario;
+use planner::parsers::{Cli, Command, OutputFormat, Runtime};
 use planner::planners;
-use planner::run;
-use planner::simulators;
+use planner::run::{self, Checkpoint};
+use planner::traits::Observer;
 use planner::types::Grid;
-use planner::utils::{create_path_traces, plot_paths, print_paths};
+use planner::utils::{
+    create_path_traces, print_paths, GifRecorder, Metrics, Outcome, Plot, Raster, Report, Svg,
+    TrajectoryCsv, Tui,
+};
 
 #[allow(unused_imports)]
 use planner::config::grids::*;
 
 use clap::Parser;
+use std::io::IsTerminal;
+use std::sync::Arc;
 
 // TODO: List of todos:
 // --docs
@@ -21,37 +27,239 @@ async fn main() {
     env_logger::init();
     log::info!("starting up");
     let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Sweep(args)) => {
+            if let Err(e) = run::run_sweep(&cli, args) {
+                log::error!("Failed the sweep: {:?}", e);
+            }
+            return;
+        }
+        Some(Command::Tune(args)) => {
+            if let Err(e) = run::run_tune(&cli, args) {
+                log::error!("Failed the tuning: {:?}", e);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    // INFO: Resume or replay a recorded run with its recorded options
+    let (cli, recording) = match cli.resume.as_deref().or(cli.replay.as_deref()) {
+        Some(path) => match Checkpoint::load(path)
+            .and_then(|recording| Ok((recording.cli(&cli)?, recording)))
+        {
+            Ok((recorded_cli, recording)) => (recorded_cli, Some(recording)),
+            Err(e) => {
+                log::error!("Failed to load the checkpoint: {:?}", e);
+                return;
+            }
+        },
+        None => (cli, None),
+    };
+    if cli.runtime != Runtime::Lockstep && (recording.is_some() || cli.checkpoint.is_some()) {
+        log::error!("Checkpoints are only supported by the lockstep runtime");
+        return;
+    }
+    let mut observers: Vec<Box<dyn Observer>> = Vec::new();
+    if let Some(path) = &cli.trajectory {
+        if cli.runtime != Runtime::Lockstep {
+            log::warn!("Trajectories are only written by the lockstep runtime");
+        }
+        match TrajectoryCsv::create(path) {
+            Ok(csv) => observers.push(Box::new(csv)),
+            Err(e) => {
+                log::error!("Failed to create the trajectory: {:?}", e);
+                return;
+            }
+        }
+    }
 
     // INFO: Starting Configuration
-    let x = cli.pos_x.clone();
-    let y = cli.pos_y.clone();
-    let positions: Vec<(usize, usize)> = x.into_iter().zip(y).collect();
-    let grid: Grid = Grid::load(&cli.grid).expect("Failed to load the grid");
-
-    let planner = planners::RayCasting {
-        len: cli.size,
-        rays: 16,
+    let scenario = match Scenario::from_cli_or_file(&cli) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            log::error!("Failed to load the scenario: {:?}", e);
+            return;
+        }
     };
-    let simulator = simulators::Incremental {
-        start_grid: grid.clone(),
-        increment_step: 1,
+    let positions = scenario.positions();
+    let grid: Grid = match &recording {
+        Some(recording) => recording.starting_grid.clone(),
+        None => Grid::load(&cli.grid).expect("Failed to load the grid"),
     };
-    let paths = match run::run_drone_simulation(cli, planner, simulator, positions.clone(), &grid) {
+    if let Err(e) = cli.check_positions(&positions, grid.size) {
+        log::error!("Invalid positions: {:?}", e);
+        return;
+    }
+    if let Some(path) = &cli.gif {
+        if cli.runtime != Runtime::Lockstep {
+            log::warn!("Animations are only recorded by the lockstep runtime");
+        }
+        match GifRecorder::create(path, &grid, cli.frame_delay, &cli.obstacle) {
+            Ok(gif) => observers.push(Box::new(gif)),
+            Err(e) => {
+                log::error!("Failed to create the animation: {:?}", e);
+                return;
+            }
+        }
+    }
+
+    let run::Setup {
+        planner,
+        simulator,
+        mut fleet,
+        territories,
+    } = run::Setup::new(&cli, scenario, &grid);
+    let reference = cli.reference.then_some((cli.tour_iterations, cli.seed));
+    let replaying = cli.replay.is_some();
+    let (format, time_steps, grid_name) = (cli.format, cli.time_steps, cli.grid.clone());
+    let mark = cli.mark;
+    let (svg, png, obstacles) = (cli.svg.clone(), cli.png.clone(), cli.obstacle.clone());
+    if cli.tui {
+        if cli.runtime != Runtime::Lockstep {
+            log::warn!("The terminal UI is only shown by the lockstep runtime");
+        }
+        match Tui::start(time_steps) {
+            Ok(tui) => observers.push(Box::new(tui)),
+            Err(e) => {
+                log::error!("Failed to start the terminal UI: {:?}", e);
+                return;
+            }
+        }
+    }
+    let result = match cli.runtime {
+        Runtime::Lockstep => {
+            let schedule = cli.fleet_schedule();
+            let mut checkpoint = match &recording {
+                Some(recording) if cli.resume.is_some() => {
+                    log::info!("Resuming the run from step {}", recording.step);
+                    recording.clone()
+                }
+                Some(recording) => Checkpoint::start(recording.args.clone(), &cli, &grid, fleet),
+                None => Checkpoint::start(std::env::args().collect(), &cli, &grid, fleet),
+            };
+            run::resume_fleet_simulation(
+                &cli,
+                &planner,
+                &simulator,
+                &schedule,
+                &mut checkpoint,
+                &mut observers,
+            )
+            .map(|_| {
+                checkpoint.fleet.log_lifetimes();
+                let steps = checkpoint.step;
+                (
+                    checkpoint.fleet.into_results(),
+                    steps,
+                    Some(checkpoint.grid),
+                )
+            })
+        }
+        Runtime::EventDriven => {
+            let config = run::EventDrivenConfig::from_cli(&cli);
+            let drones = fleet.active().into_iter().map(|(_, drone)| drone).collect();
+            run::run_event_driven_simulation(cli, config, planner, simulator, drones, &grid)
+                .await
+                .map(|(results, steps)| (results, steps, None))
+        }
+        Runtime::RealTime => {
+            let config = run::RealTimeConfig::from_cli(&cli);
+            let schedule = cli.fleet_schedule();
+            let planner = Arc::new(planner);
+            run::run_real_time_simulation(
+                &cli, config, planner, &simulator, &mut fleet, &schedule, &grid,
+            )
+            .await
+            .map(|(final_grid, steps)| {
+                fleet.log_lifetimes();
+                (fleet.into_results(), steps, Some(final_grid))
+            })
+        }
+    };
+    let (results, steps, final_grid) = match result {
         Ok(result_path) => result_path,
-        Err(_) => {
-            log::error!("Failed the simulation");
+        Err(e) => {
+            log::error!("Failed the simulation: {:?}", e);
+            if format == OutputFormat::Json {
+                let outcome = Outcome::Failed {
+                    error: format!("{:#}", e),
+                };
+                let report = Report::new(&grid_name, &grid, &[], &[], time_steps, 0, outcome);
+                print_report(&report);
+            }
             return;
         }
     };
 
+    if let Some(recording) = recording.filter(|_| replaying) {
+        match recording.verify(&results.paths) {
+            Ok(()) => log::info!("Replay reproduced the {} recorded steps", recording.step),
+            Err(e) => log::error!("Replay differs from the recording: {:?}", e),
+        }
+    }
+
     // INFO: End
     // Aggregate the results and format them for printing
-    let path_traces = create_path_traces(&paths, &positions, &grid);
+    let path_traces = create_path_traces(&results.paths, &results.rewards);
+    let metrics = Metrics::new(&grid, &results);
+
+    if let Some((iterations, seed)) = reference {
+        planners::orienteering::log_reference(&grid, &path_traces, iterations, seed);
+    }
+
+    if let Some(file) = svg {
+        let mut figure = Svg::new(&grid, &path_traces).with_obstacles(&obstacles);
+        if let Some(territories) = &territories {
+            figure = figure.with_territories(territories);
+        }
+        if let Err(e) = figure.save(&file) {
+            log::error!("Failed to draw the figure: {:?}", e);
+        }
+    }
+    if let Some(file) = png {
+        let image = Raster::new(final_grid.as_ref().unwrap_or(&grid), &path_traces)
+            .with_obstacles(&obstacles);
+        if let Err(e) = image.save_png(&file) {
+            log::error!("Failed to draw the image: {:?}", e);
+        }
+    }
+
+    match format {
+        OutputFormat::Text if !path_traces.is_empty() => {
+            let plot = Plot::new(&grid, &path_traces)
+                .marking(mark)
+                .coloured(std::io::stderr().is_terminal());
+            log::info!("Paths on grid:\n{}", plot.render().trim_end());
+            print_paths(&path_traces);
+            metrics.log();
+        }
+        OutputFormat::Text => log::debug!("No path found"),
+        OutputFormat::Json => {
+            let outcome = if steps < time_steps {
+                Outcome::Stopped
+            } else {
+                Outcome::Completed
+            };
+            let report = Report::new(
+                &grid_name,
+                &grid,
+                &path_traces,
+                &results.rewards,
+                time_steps,
+                steps,
+                outcome,
+            )
+            .with_metrics(metrics);
+            print_report(&report);
+        }
+    }
+}
 
-    if !path_traces.is_empty() {
-        plot_paths(&grid, &path_traces);
-        print_paths(&path_traces);
-    } else {
-        log::debug!("No path found");
+/// Prints the report of a run as JSON to stdout.
+fn print_report(report: &Report) {
+    match report.to_json() {
+        Ok(json) => println!("{}", json),
+        Err(e) => log::error!("Failed to serialise the report: {:?}", e),
     }
 }
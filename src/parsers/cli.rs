@@ -1,7 +1,13 @@
-use clap::Parser;
+use crate::energy::EnergyModel;
+use crate::run::tune::{Objective, Search};
+use crate::run::{FleetChange, FleetEvent};
+use crate::types::{Footprint, Obstacle};
+use crate::utils::{Estimator, Marker};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::time::Duration;
 
 /// CLI Parser to configure the planner
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 #[command(
     author = "Deniz Hofmeister",
     version = "0.1.0",
@@ -9,6 +15,10 @@ use clap::Parser;
     long_about = None
 )]
 pub struct Cli {
+    /// Run something else than a single simulation
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Grid size
     #[arg(short = 'n', long, default_value = "5")]
     pub size: usize,
@@ -21,6 +31,14 @@ pub struct Cli {
     #[arg(short = 'T', long, default_value = "100")]
     pub max_duration: usize,
 
+    /// How the duration of the next step is estimated
+    #[arg(long, value_enum, default_value_t = Estimator::Ema)]
+    pub deadline_estimator: Estimator,
+
+    /// Fraction added to the estimated step duration to be safe
+    #[arg(long, default_value = "0.2", value_parser = parse_non_negative)]
+    pub safety_margin: f32,
+
     /// Starting positions x
     #[arg(short = 'x', long, value_parser = parse_position, number_of_values = 1, action = clap::ArgAction::Append)]
     pub pos_x: Vec<usize>,
@@ -32,8 +50,624 @@ pub struct Cli {
     /// Source grid
     #[arg(short = 'g', long, default_value = "GRID_S")]
     pub grid: String,
+
+    /// Value every cell regrows per step, up to its starting value
+    #[arg(long, default_value = "1")]
+    pub increment_step: u8,
+
+    /// Planning threads [default: available parallelism]
+    #[arg(short = 'j', long)]
+    pub jobs: Option<usize>,
+
+    /// Simulation runtime
+    #[arg(short = 'r', long, value_enum, default_value_t = Runtime::Lockstep)]
+    pub runtime: Runtime,
+
+    /// World clock period in ms (event-driven runtime)
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    pub world_period: u64,
+
+    /// Planning period in ms per drone (event-driven runtime) [default: world period]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), number_of_values = 1, action = clap::ArgAction::Append)]
+    pub drone_period: Vec<u64>,
+
+    /// Drone to world message latency in ms (event-driven runtime)
+    #[arg(long, default_value = "0")]
+    pub latency: u64,
+
+    /// Steps per second (real-time runtime)
+    #[arg(long, default_value = "5", value_parser = parse_tick_rate)]
+    pub tick_rate: f32,
+
+    /// What happens to a step whose plans are late (real-time runtime)
+    #[arg(long, value_enum, default_value_t = OverrunPolicy::Hold)]
+    pub overrun: OverrunPolicy,
+
+    /// Drone joining mid-run, as STEP:X:Y
+    #[arg(long, value_parser = parse_join, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub join: Vec<FleetEvent>,
+
+    /// Drone leaving mid-run, as STEP:DRONE
+    #[arg(long, value_parser = parse_leave, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub leave: Vec<FleetEvent>,
+
+    /// Battery capacity per drone, enables the energy model
+    #[arg(long, value_parser = parse_positive)]
+    pub battery: Option<f32>,
+
+    /// Charging station, as X:Y [default: starting positions]
+    #[arg(long, value_parser = parse_station, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub station: Vec<(usize, usize)>,
+
+    /// Charge used per straight move
+    #[arg(long, default_value = "1", value_parser = parse_non_negative)]
+    pub straight_cost: f32,
+
+    /// Charge used per diagonal move
+    #[arg(long, default_value = "1.4", value_parser = parse_non_negative)]
+    pub diagonal_cost: f32,
+
+    /// Charge regained per step on a station
+    #[arg(long, default_value = "10", value_parser = parse_positive)]
+    pub charge_rate: f32,
+
+    /// Scenario file with drone profiles and starting positions, replaces -x/-y (JSON)
+    #[arg(short = 's', long)]
+    pub scenario: Option<String>,
+
+    /// Planner per drone [default: ray-casting]
+    #[arg(long, value_enum, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub planner: Vec<PlannerKind>,
+
+    /// Min distance between frontiers claimed by different drones
+    #[arg(long, default_value = "3")]
+    pub frontier_separation: usize,
+
+    /// Cell the orienteering tour of each drone ends at, as X:Y [default: anywhere]
+    #[arg(long, value_parser = parse_station, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub tour_end: Vec<(usize, usize)>,
+
+    /// Local search iterations of the orienteering planner
+    #[arg(long, default_value = "200")]
+    pub tour_iterations: usize,
+
+    /// Compare what each drone collected to an orienteering tour of the same length
+    #[arg(long)]
+    pub reference: bool,
+
+    /// Moves planned ahead by the genetic and annealing planners
+    #[arg(long, default_value = "16")]
+    pub horizon: usize,
+
+    /// Time budget of a single genetic or annealing plan in ms
+    #[arg(long, default_value = "5")]
+    pub plan_budget: usize,
+
+    /// Sequences per generation of the genetic planner
+    #[arg(long, default_value = "32")]
+    pub population: usize,
+
+    /// Max generations of the genetic planner
+    #[arg(long, default_value = "50")]
+    pub generations: usize,
+
+    /// Probability that a move is mutated by the genetic planner
+    #[arg(long, default_value = "0.1", value_parser = parse_probability)]
+    pub mutation_rate: f32,
+
+    /// Max iterations of the annealing planner
+    #[arg(long, default_value = "1000")]
+    pub annealing_iterations: usize,
+
+    /// Starting temperature of the annealing planner
+    #[arg(long, default_value = "50")]
+    pub temperature: f32,
+
+    /// Sweep the grid in lanes split among the drones, replaces the per-drone planners
+    #[arg(long)]
+    pub coverage: bool,
+
+    /// Columns covered by a single coverage lane
+    #[arg(long, default_value = "1", requires = "coverage")]
+    pub swath: usize,
+
+    /// Obstacle the coverage sweep avoids, as X:Y:HEIGHT:WIDTH
+    #[arg(long, value_parser = parse_obstacle, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub obstacle: Vec<Obstacle>,
+
+    /// Ray length per drone [default: size]
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub ray_len: Vec<usize>,
+
+    /// Number of rays per drone [default: 16]
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub rays: Vec<usize>,
+
+    /// Empty cells added to every ray when averaging its value, favouring rays away from the edge
+    #[arg(long, default_value = "2", value_parser = parse_non_negative)]
+    pub edge_bias: f32,
+
+    /// Weight of every cell of a ray relative to the previous one, below 1 favours close values
+    #[arg(long, default_value = "1")]
+    pub falloff: f32,
+
+    /// Cells moved per step, per drone [default: 1]
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub speed: Vec<usize>,
+
+    /// Harvest footprint per drone, as RADIUS:AMOUNT [default: 0:255]
+    #[arg(long, value_parser = parse_footprint, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub footprint: Vec<Footprint>,
+
+    /// Sensor radius, enables per-drone belief maps
+    #[arg(long)]
+    pub sensor_radius: Option<usize>,
+
+    /// Max noise added to sensed values
+    #[arg(long, default_value = "0")]
+    pub sensor_noise: u8,
+
+    /// Steps after which unobserved cells are forgotten
+    #[arg(long)]
+    pub max_age: Option<usize>,
+
+    /// Value assumed for unknown cells
+    #[arg(long, default_value = "10")]
+    pub belief_prior: u8,
+
+    /// Communication range in cells, limits map sharing to drones in range
+    #[arg(long, requires = "sensor_radius")]
+    pub comm_range: Option<usize>,
+
+    /// Relay messages along chains of drones in range
+    #[arg(long, requires = "comm_range")]
+    pub relay: bool,
+
+    /// Max cells a drone can receive per step
+    #[arg(long, requires = "comm_range")]
+    pub bandwidth: Option<usize>,
+
+    /// Probability that a link drops for a step
+    #[arg(long, default_value = "0", value_parser = parse_probability)]
+    pub comm_loss: f32,
+
+    /// Seed of the random number generator
+    #[arg(long, default_value = "0")]
+    pub seed: u64,
+
+    /// Format of the results printed to stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Where the drones are marked on their trails in the plot of the results
+    #[arg(long, value_enum, default_value_t = Marker::Current)]
+    pub mark: Marker,
+
+    /// SVG file to draw the grid and the trajectories of the drones to
+    #[arg(long)]
+    pub svg: Option<String>,
+
+    /// PNG file to draw the final grid and the trajectories of the drones to
+    #[arg(long)]
+    pub png: Option<String>,
+
+    /// Animated GIF file of the grid and the drones, one frame per step (lockstep runtime)
+    #[arg(long)]
+    pub gif: Option<String>,
+
+    /// Time every frame of the animation is shown, in ms
+    #[arg(long, default_value = "100", requires = "gif")]
+    pub frame_delay: u64,
+
+    /// CSV file to write the trajectory of every drone to, one row per step and drone (lockstep runtime)
+    #[arg(long)]
+    pub trajectory: Option<String>,
+
+    /// Show the run live in a full-screen terminal UI (lockstep runtime)
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Directory to save checkpoints of the run to (lockstep runtime)
+    #[arg(long)]
+    pub checkpoint: Option<String>,
+
+    /// Steps between checkpoints, the last step is always saved
+    #[arg(long, default_value = "1", requires = "checkpoint")]
+    pub checkpoint_every: usize,
+
+    /// Checkpoint to resume a run from, the run keeps its recorded options
+    #[arg(long, conflicts_with = "replay")]
+    pub resume: Option<String>,
+
+    /// Checkpoint whose run is executed again from the start and verified against it
+    #[arg(long)]
+    pub replay: Option<String>,
+}
+
+impl Cli {
+    /// Returns the joining and leaving drones as one schedule, ordered by step.
+    ///
+    /// Joins come before leaves within a step, so a drone can leave the step it joined.
+    pub fn fleet_schedule(&self) -> Vec<FleetEvent> {
+        let mut schedule: Vec<FleetEvent> = self.join.iter().chain(&self.leave).copied().collect();
+        schedule.sort_by_key(|event| event.step);
+        schedule
+    }
+
+    /// Checks that the starting positions, the positions of joining drones and the charging
+    /// stations are on the grid, and that drones with a battery can reach a station from where
+    /// they launch.
+    ///
+    /// # Arguments
+    ///
+    /// * `starting_positions` - The starting positions of the drones
+    /// * `grid_size` - The number of rows and columns of the grid
+    ///
+    /// # Returns
+    ///
+    /// An error naming the first position outside the grid or out of reach of every station
+    pub fn check_positions(
+        &self,
+        starting_positions: &[(usize, usize)],
+        grid_size: usize,
+    ) -> anyhow::Result<()> {
+        let joins = self.join.iter().filter_map(|event| match event.change {
+            FleetChange::Join(position) => Some(("Joining drone", position)),
+            FleetChange::Leave(_) => None,
+        });
+        let launches: Vec<(&str, (usize, usize))> = starting_positions
+            .iter()
+            .map(|&position| ("Starting position", position))
+            .chain(joins)
+            .collect();
+        let stations = self.station.iter().map(|&position| ("Station", position));
+        for (what, (x, y)) in launches.iter().copied().chain(stations) {
+            if x >= grid_size || y >= grid_size {
+                anyhow::bail!(
+                    "{} ({}, {}) is outside the {}x{} grid",
+                    what,
+                    x,
+                    y,
+                    grid_size,
+                    grid_size
+                );
+            }
+        }
+
+        if let Some(model) = self.energy_model(starting_positions) {
+            for (what, position) in launches {
+                let cost = model.return_cost(position);
+                if cost > model.capacity {
+                    anyhow::bail!(
+                        "{} {:?} needs {:.1} charge to reach a station, the battery holds {:.1}",
+                        what,
+                        position,
+                        cost,
+                        model.capacity
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the energy model if drones have a battery.
+    ///
+    /// Without explicit stations, drones charge at their starting positions.
+    pub fn energy_model(&self, starting_positions: &[(usize, usize)]) -> Option<EnergyModel> {
+        let stations = if self.station.is_empty() {
+            starting_positions.to_vec()
+        } else {
+            self.station.clone()
+        };
+
+        self.battery.map(|capacity| EnergyModel {
+            capacity,
+            straight_cost: self.straight_cost,
+            diagonal_cost: self.diagonal_cost,
+            charge_rate: self.charge_rate,
+            stations,
+        })
+    }
+}
+
+/// The commands run instead of a single simulation
+#[derive(Clone, Debug, Subcommand)]
+pub enum Command {
+    /// Run every combination of the swept parameters and tabulate their metrics
+    Sweep(SweepArgs),
+    /// Search the ray casting parameters maximising a metric and write them as a scenario file
+    Tune(TuneArgs),
+}
+
+/// The parameters of a sweep, every other option is taken from the main command line.
+///
+/// A parameter that is not swept keeps the value of the main command line.
+#[derive(Clone, Debug, Args)]
+pub struct SweepArgs {
+    /// Source grids
+    #[arg(short = 'g', long = "grid", number_of_values = 1, action = clap::ArgAction::Append)]
+    pub grids: Vec<String>,
+
+    /// Numbers of rays of the ray casting planner
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub rays: Vec<usize>,
+
+    /// Ray lengths of the ray casting planner
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub ray_len: Vec<usize>,
+
+    /// Edge biases of the ray casting planner
+    #[arg(long, value_parser = parse_non_negative, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub edge_bias: Vec<f32>,
+
+    /// Falloffs of the ray casting planner
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub falloff: Vec<f32>,
+
+    /// Values every cell regrows per step
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub increment_step: Vec<u8>,
+
+    /// Numbers of drones, taken from the start of every set of starting positions
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub drones: Vec<usize>,
+
+    /// Set of starting positions, as X:Y,X:Y,... [default: the positions of the main command line]
+    #[arg(long, value_parser = parse_positions, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub starts: Vec<Vec<(usize, usize)>>,
+
+    /// Seeds
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub seed: Vec<u64>,
+
+    /// Runs at the same time [default: available parallelism]
+    #[arg(short = 'p', long)]
+    pub parallel: Option<usize>,
+
+    /// Format of the table
+    #[arg(long, value_enum, default_value_t = TableFormat::Csv)]
+    pub table: TableFormat,
+
+    /// File to write the table to [default: stdout]
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+/// The options of the tuner, the drones and every other option are taken from the main command
+/// line.
+#[derive(Clone, Debug, Args)]
+pub struct TuneArgs {
+    /// Metric to maximise
+    #[arg(long, value_enum, default_value_t = Objective::Reward)]
+    pub objective: Objective,
+
+    /// How candidates are picked
+    #[arg(long, value_enum, default_value_t = Search::Evolution)]
+    pub search: Search,
+
+    /// Candidates to evaluate
+    #[arg(short = 'e', long, default_value = "64")]
+    pub evaluations: usize,
+
+    /// Candidates evaluated together, the generation size of the evolution strategy
+    #[arg(short = 'b', long, default_value = "8")]
+    pub batch: usize,
+
+    /// Range of the number of rays, as MIN:MAX
+    #[arg(long, value_parser = parse_range, default_value = "4:32")]
+    pub rays: (f32, f32),
+
+    /// Range of the ray length, as MIN:MAX
+    #[arg(long, value_parser = parse_range, default_value = "2:20")]
+    pub ray_len: (f32, f32),
+
+    /// Range of the edge bias, as MIN:MAX
+    #[arg(long, value_parser = parse_non_negative_range, default_value = "0:8")]
+    pub edge_bias: (f32, f32),
+
+    /// Range of the falloff, as MIN:MAX
+    #[arg(long, value_parser = parse_range, default_value = "0.5:1")]
+    pub falloff: (f32, f32),
+
+    /// Grids every candidate is run on [default: the grid of the main command line]
+    #[arg(short = 'g', long = "grid", number_of_values = 1, action = clap::ArgAction::Append)]
+    pub grids: Vec<String>,
+
+    /// Seeds every candidate is run with [default: the seed of the main command line]
+    #[arg(long, number_of_values = 1, action = clap::ArgAction::Append)]
+    pub seed: Vec<u64>,
+
+    /// Seed of the search
+    #[arg(long, default_value = "0")]
+    pub search_seed: u64,
+
+    /// Runs at the same time [default: available parallelism]
+    #[arg(short = 'p', long)]
+    pub parallel: Option<usize>,
+
+    /// Scenario file to write the best configuration to [default: stdout]
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+/// The formats the table of a sweep can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TableFormat {
+    /// One row per run with the summary metrics
+    Csv,
+    /// One object per run with all metrics, including the curves
+    Json,
+}
+
+/// The available simulation runtimes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Runtime {
+    /// All drones plan and move in lockstep with the simulator
+    Lockstep,
+    /// Every drone is a task exchanging messages with a clocked world
+    EventDriven,
+    /// All drones plan and move in lockstep, paced to a wall-clock tick rate
+    RealTime,
+}
+
+/// The formats the results of a run can be printed in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// The paths of every drone, one per line
+    Text,
+    /// A versioned JSON report of the run
+    Json,
+}
+
+/// What the real-time runtime does with a step whose plans are not ready by the end of its tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OverrunPolicy {
+    /// Drop the late plans, the drones stay put until the next tick
+    Skip,
+    /// Drop the late plans, the drones keep following their last plan until the next tick
+    Hold,
+    /// Apply the late plans and run the following steps back to back until on schedule again
+    CatchUp,
+}
+
+/// The planners that can be chosen per drone on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PlannerKind {
+    /// Cast rays and follow the most rewarding one
+    RayCasting,
+    /// Explore frontiers of the belief map, then cast rays
+    Frontier,
+    /// Plan the most rewarding tour within the time steps once, then follow it
+    Orienteering,
+    /// Evolve sequences of moves with a genetic algorithm
+    Genetic,
+    /// Improve a sequence of moves by simulated annealing
+    Annealing,
 }
 
 pub fn parse_position(s: &str) -> Result<usize, String> {
     s.parse().map_err(|_| format!("Invalid number: {}", s))
 }
+
+pub fn parse_join(s: &str) -> Result<FleetEvent, String> {
+    match *parse_fields(s)?.as_slice() {
+        [0, _, _] => Err(format!("Steps start at 1, got: {}", s)),
+        [step, x, y] => Ok(FleetEvent {
+            step,
+            change: FleetChange::Join((x, y)),
+        }),
+        _ => Err(format!("Expected STEP:X:Y, got: {}", s)),
+    }
+}
+
+pub fn parse_leave(s: &str) -> Result<FleetEvent, String> {
+    match *parse_fields(s)?.as_slice() {
+        [0, _] => Err(format!("Steps start at 1, got: {}", s)),
+        [step, drone] => Ok(FleetEvent {
+            step,
+            change: FleetChange::Leave(drone),
+        }),
+        _ => Err(format!("Expected STEP:DRONE, got: {}", s)),
+    }
+}
+
+fn parse_fields(s: &str) -> Result<Vec<usize>, String> {
+    s.split(':').map(parse_position).collect()
+}
+
+pub fn parse_station(s: &str) -> Result<(usize, usize), String> {
+    match parse_fields(s)?.as_slice() {
+        &[x, y] => Ok((x, y)),
+        _ => Err(format!("Expected X:Y, got: {}", s)),
+    }
+}
+
+pub fn parse_positions(s: &str) -> Result<Vec<(usize, usize)>, String> {
+    s.split(',')
+        .map(|position| parse_station(position.trim()))
+        .collect()
+}
+
+pub fn parse_range(s: &str) -> Result<(f32, f32), String> {
+    let bounds: Vec<f32> = s
+        .split(':')
+        .map(|bound| {
+            bound
+                .parse()
+                .map_err(|_| format!("Invalid number: {}", bound))
+        })
+        .collect::<Result<_, _>>()?;
+    match *bounds.as_slice() {
+        [min, max] if min <= max => Ok((min, max)),
+        [_, _] => Err(format!("Expected MIN <= MAX, got: {}", s)),
+        _ => Err(format!("Expected MIN:MAX, got: {}", s)),
+    }
+}
+
+pub fn parse_non_negative_range(s: &str) -> Result<(f32, f32), String> {
+    match parse_range(s)? {
+        (min, _) if min < 0. => Err(format!("Expected MIN of at least 0, got: {}", s)),
+        range => Ok(range),
+    }
+}
+
+pub fn parse_tick_rate(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(rate) if rate.is_finite() && Duration::try_from_secs_f32(1. / rate).is_ok() => Ok(rate),
+        Ok(_) => Err(format!(
+            "Expected a finite, positive number of steps per second, got: {}",
+            s
+        )),
+        Err(_) => Err(format!("Invalid number: {}", s)),
+    }
+}
+
+pub fn parse_positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() && value > 0. => Ok(value),
+        Ok(_) => Err(format!("Expected a finite, positive number, got: {}", s)),
+        Err(_) => Err(format!("Invalid number: {}", s)),
+    }
+}
+
+pub fn parse_non_negative(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() && value >= 0. => Ok(value),
+        Ok(_) => Err(format!(
+            "Expected a finite number of at least 0, got: {}",
+            s
+        )),
+        Err(_) => Err(format!("Invalid number: {}", s)),
+    }
+}
+
+pub fn parse_probability(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if (0. ..=1.).contains(&value) => Ok(value),
+        Ok(_) => Err(format!("Expected a probability from 0 to 1, got: {}", s)),
+        Err(_) => Err(format!("Invalid number: {}", s)),
+    }
+}
+
+pub fn parse_footprint(s: &str) -> Result<Footprint, String> {
+    match parse_fields(s)?.as_slice() {
+        &[radius, amount] => Ok(Footprint {
+            radius,
+            amount: u8::try_from(amount).map_err(|_| format!("Invalid amount: {}", amount))?,
+        }),
+        _ => Err(format!("Expected RADIUS:AMOUNT, got: {}", s)),
+    }
+}
+
+pub fn parse_obstacle(s: &str) -> Result<Obstacle, String> {
+    match parse_fields(s)?.as_slice() {
+        &[x, y, height, width] => Ok(Obstacle {
+            x,
+            y,
+            height,
+            width,
+        }),
+        _ => Err(format!("Expected X:Y:HEIGHT:WIDTH, got: {}", s)),
+    }
+}
@@ -1,2 +1,5 @@
 pub mod cli;
-pub use cli::Cli;
+pub use cli::{
+    Cli, Command, OutputFormat, OverrunPolicy, PlannerKind, Runtime, SweepArgs, TableFormat,
+    TuneArgs,
+};
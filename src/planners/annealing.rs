@@ -0,0 +1,143 @@
+use crate::planners::moves;
+use crate::traits::{Planner, Simulator};
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::{Budget, Deadline, Rng};
+use std::time::Duration;
+
+/// A planner improving a sequence of moves by simulated annealing.
+///
+/// Every sequence is valued by simulating the drone flying it, so regrowth is accounted for.
+/// Every iteration replaces a single move. Better sequences are always accepted, worse ones with
+/// a probability that shrinks as the temperature cools down linearly to zero. The search is
+/// anytime: it stops when the iterations or the time budget run out and returns the best
+/// sequence so far.
+pub struct Annealing<S> {
+    /// The simulator predicting the outcome of a sequence.
+    pub simulator: S,
+    /// The number of moves of a sequence.
+    pub horizon: usize,
+    /// The maximum number of iterations.
+    pub iterations: usize,
+    /// The starting temperature, in harvested value.
+    pub temperature: f32,
+    /// The time budget of a single call in ms.
+    pub budget: usize,
+    /// The seed of the searches, mixed with the drone and grid of every call, see `moves::seed`.
+    pub seed: u64,
+}
+
+impl<S: Simulator> Planner for Annealing<S> {
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.plan(grid, &DroneState::new(start))
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.plan_within(grid, drone, &Budget::unlimited())
+    }
+
+    /// Anneals the sequence of moves harvesting the most within the horizon.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the call may take, on top of the budget of the planner.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` with the best sequence found, its cost being the value harvested, or
+    /// `None` if it does not move.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        let time = budget.cap(Duration::from_millis(self.budget as u64));
+        let mut deadline = Deadline::new(time.as_secs_f32() * 1000.);
+        let mut rng = Rng::new(moves::seed(self.seed, grid, drone));
+        let fitness = |sequence: &[u8]| {
+            let cells = moves::decode(drone.position, sequence, grid.size);
+            moves::evaluate(&self.simulator, grid, drone, &cells)
+        };
+
+        let mut current = moves::random(&mut rng, self.horizon);
+        let mut current_harvested = fitness(&current);
+        let mut best = (current.clone(), current_harvested);
+
+        for iteration in 0..self.iterations {
+            if current.is_empty() {
+                break;
+            }
+            let temperature = self.temperature * (1. - iteration as f32 / self.iterations as f32);
+
+            let mut candidate = current.clone();
+            let gene = rng.below(candidate.len());
+            candidate[gene] = rng.below(moves::MOVES.len()) as u8;
+            let harvested = fitness(&candidate);
+
+            let delta = harvested as f32 - current_harvested as f32;
+            if delta >= 0. || rng.next_f32() < (delta / temperature.max(f32::EPSILON)).exp() {
+                current = candidate;
+                current_harvested = harvested;
+                if current_harvested > best.1 {
+                    best = (current.clone(), current_harvested);
+                }
+            }
+
+            deadline.tick();
+            if deadline.will_exceed_deadline() || budget.is_aborted() {
+                log::debug!("Annealing planner out of time");
+                break;
+            }
+        }
+
+        moves::path(moves::decode(drone.position, &best.0, grid.size), best.1)
+    }
+
+    /// Returns `true`, as the search stops when its time budget runs out.
+    fn depends_on_time(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulators::Incremental;
+
+    /// Tests that annealed sequences are valid moves and beat a straight line.
+    #[test]
+    fn test_annealing_plan() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let simulator = Incremental {
+            increment_step: 1,
+            start_grid: grid.clone(),
+        };
+        let mut planner = Annealing {
+            simulator,
+            horizon: 8,
+            iterations: 500,
+            temperature: 50.,
+            budget: 10_000,
+            seed: 0,
+        };
+        let drone = DroneState::new((5, 5));
+
+        let path = planner.plan(&grid, &drone).expect("Planner should move");
+        let cells: Vec<(usize, usize)> = path.steps.iter().copied().collect();
+        assert!(cells.len() <= 9);
+        for pair in cells.windows(2) {
+            assert_eq!(
+                pair[0]
+                    .0
+                    .abs_diff(pair[1].0)
+                    .max(pair[0].1.abs_diff(pair[1].1)),
+                1
+            );
+        }
+        let straight = moves::decode((5, 5), &[0; 8], grid.size);
+        assert!(path.total_cost >= moves::evaluate(&planner.simulator, &grid, &drone, &straight));
+
+        planner.budget = 0;
+        assert!(
+            planner.plan(&grid, &drone).is_some(),
+            "Best sequence so far is returned when out of time"
+        );
+    }
+}
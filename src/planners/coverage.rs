@@ -0,0 +1,443 @@
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Obstacle, Path};
+use bresenham::Bresenham;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A region of free space that can be swept without crossing obstacles.
+///
+/// Every column of the region holds a single contiguous range of free rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// The first column of the region.
+    pub first_col: usize,
+    /// The first and last free row of every column of the region, starting at `first_col`.
+    pub rows: Vec<(usize, usize)>,
+}
+
+/// A deterministic planner sweeping the free space of the grid in back and forth lanes.
+///
+/// The free space is decomposed into regions around the obstacles, every region is swept in
+/// boustrophedon lanes `swath` columns apart, and the resulting sweep is cut into pieces of
+/// equal length, one per drone. Every drone flies to the start of the piece closest to it and
+/// then follows it, regardless of the values of the grid.
+pub struct Coverage {
+    /// The size of the grid.
+    size: usize,
+    /// Whether each cell is free of obstacles.
+    free: Vec<bool>,
+    /// The route of every drone, by drone id, starting at its starting position.
+    routes: Vec<Vec<(usize, usize)>>,
+    /// The index of the route cell every drone last planned from.
+    progress: Mutex<Vec<usize>>,
+}
+
+impl Coverage {
+    /// Plans the sweep of a grid and splits it among the drones.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the grid.
+    /// * `obstacles` - The areas drones cannot fly over.
+    /// * `swath` - The number of columns covered by a single lane.
+    /// * `starts` - The starting positions of the drones, in drone id order.
+    pub fn new(
+        size: usize,
+        obstacles: &[Obstacle],
+        swath: usize,
+        starts: &[(usize, usize)],
+    ) -> Self {
+        let free = (0..size * size)
+            .map(|index| {
+                let (x, y) = (index / size, index % size);
+                !obstacles.iter().any(|obstacle| obstacle.contains(x, y))
+            })
+            .collect();
+        let mut coverage = Coverage {
+            size,
+            free,
+            routes: Vec::new(),
+            progress: Mutex::new(vec![0; starts.len()]),
+        };
+
+        let sweep = coverage.sweep(swath.max(1));
+        coverage.routes = coverage.split(&sweep, starts);
+        coverage
+    }
+
+    /// Returns the route of every drone, by drone id.
+    pub fn routes(&self) -> &[Vec<(usize, usize)>] {
+        &self.routes
+    }
+
+    /// Returns the drone whose route passes every cell, indexed like the grid data.
+    ///
+    /// Cells on several routes belong to the drone with the highest id.
+    pub fn territories(&self) -> Vec<Option<usize>> {
+        let mut territories = vec![None; self.size * self.size];
+        for (drone, route) in self.routes.iter().enumerate() {
+            for &(x, y) in route {
+                territories[x * self.size + y] = Some(drone);
+            }
+        }
+        territories
+    }
+
+    /// Returns the whole route of every drone as a single path, as consumed by the plotter.
+    pub fn paths(&self, grid: &Grid) -> Vec<Vec<Path>> {
+        self.routes
+            .iter()
+            .map(|route| vec![Self::path(grid, route.clone())])
+            .collect()
+    }
+
+    /// Decomposes the free space into regions (boustrophedon cell decomposition).
+    ///
+    /// Columns are scanned from left to right. A range of free rows continues the region of a
+    /// range in the previous column if the two ranges only overlap each other, otherwise it
+    /// starts a new region, so regions split and merge around obstacles.
+    pub fn decompose(&self) -> Vec<Region> {
+        let mut regions: Vec<Region> = Vec::new();
+        // The free ranges of the previous column, with the region each belongs to
+        let mut previous: Vec<((usize, usize), usize)> = Vec::new();
+
+        for col in 0..self.size {
+            let ranges = self.free_ranges(col);
+            let mut current = Vec::with_capacity(ranges.len());
+            for &range in &ranges {
+                let overlapping: Vec<usize> = (0..previous.len())
+                    .filter(|&i| overlaps(previous[i].0, range))
+                    .collect();
+                let continued = match overlapping.as_slice() {
+                    &[only] => {
+                        let splits = ranges
+                            .iter()
+                            .filter(|&&other| overlaps(previous[only].0, other))
+                            .count();
+                        (splits == 1).then_some(previous[only].1)
+                    }
+                    _ => None,
+                };
+
+                let region = match continued {
+                    Some(region) => {
+                        regions[region].rows.push(range);
+                        region
+                    }
+                    None => {
+                        regions.push(Region {
+                            first_col: col,
+                            rows: vec![range],
+                        });
+                        regions.len() - 1
+                    }
+                };
+                current.push((range, region));
+            }
+            previous = current;
+        }
+
+        regions
+    }
+
+    /// Returns the ranges of free rows of a column, from top to bottom.
+    fn free_ranges(&self, col: usize) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for row in 0..self.size {
+            match (self.is_free(row, col), start) {
+                (true, None) => start = Some(row),
+                (false, Some(first)) => {
+                    ranges.push((first, row - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(first) = start {
+            ranges.push((first, self.size - 1));
+        }
+        ranges
+    }
+
+    /// Returns whether a cell is free of obstacles.
+    fn is_free(&self, x: usize, y: usize) -> bool {
+        self.free[x * self.size + y]
+    }
+
+    /// Sweeps all regions, visiting the closest unswept region next.
+    fn sweep(&self, swath: usize) -> Vec<(usize, usize)> {
+        let mut remaining: Vec<Vec<(usize, (usize, usize))>> = self
+            .decompose()
+            .iter()
+            .map(|region| lanes(region, swath))
+            .collect();
+        let mut route = Vec::new();
+
+        while !remaining.is_empty() {
+            let next = match route.last() {
+                None => 0,
+                Some(&position) => (0..remaining.len())
+                    .min_by_key(|&i| {
+                        let (col, (top, bottom)) = remaining[i][0];
+                        distance(position, (top, col)).min(distance(position, (bottom, col)))
+                    })
+                    .unwrap_or(0),
+            };
+
+            for (col, (top, bottom)) in remaining.remove(next) {
+                // Enter every lane from the end closest to the drone
+                let downwards = route
+                    .last()
+                    .is_none_or(|&(x, _): &(usize, usize)| x.abs_diff(top) <= x.abs_diff(bottom));
+                let rows: Vec<usize> = if downwards {
+                    (top..=bottom).collect()
+                } else {
+                    (top..=bottom).rev().collect()
+                };
+
+                self.extend_to(&mut route, (rows[0], col));
+                route.extend(rows[1..].iter().map(|&row| (row, col)));
+            }
+        }
+
+        route
+    }
+
+    /// Cuts the sweep into pieces of equal length and gives every drone the closest piece.
+    fn split(
+        &self,
+        sweep: &[(usize, usize)],
+        starts: &[(usize, usize)],
+    ) -> Vec<Vec<(usize, usize)>> {
+        let mut routes: Vec<Vec<(usize, usize)>> =
+            starts.iter().map(|&start| vec![start]).collect();
+        if starts.is_empty() || sweep.is_empty() {
+            return routes;
+        }
+
+        let mut unassigned: Vec<usize> = (0..starts.len()).collect();
+        for piece in sweep.chunks(sweep.len().div_ceil(starts.len())) {
+            let closest = (0..unassigned.len())
+                .min_by_key(|&i| distance(starts[unassigned[i]], piece[0]))
+                .unwrap_or(0);
+            let route = &mut routes[unassigned.remove(closest)];
+            self.extend_to(route, piece[0]);
+            route.extend_from_slice(&piece[1..]);
+        }
+
+        routes
+    }
+
+    /// Extends a route with the shortest path to a goal over free cells.
+    ///
+    /// If no such path exists, the drone flies straight to the goal.
+    fn extend_to(&self, route: &mut Vec<(usize, usize)>, goal: (usize, usize)) {
+        let Some(&start) = route.last() else {
+            route.push(goal);
+            return;
+        };
+        if start == goal {
+            return;
+        }
+
+        match self.shortest_path(start, goal) {
+            Some(path) => route.extend(path),
+            None => {
+                let line = Bresenham::new(
+                    (start.0 as isize, start.1 as isize),
+                    (goal.0 as isize, goal.1 as isize),
+                );
+                route.extend(line.skip(1).map(|(x, y)| (x as usize, y as usize)));
+                route.push(goal);
+            }
+        }
+    }
+
+    /// Finds the shortest path between two cells over free cells, moving in 8 directions.
+    ///
+    /// # Returns
+    ///
+    /// The cells after `start` up to and including `goal`, or `None` if the goal is unreachable.
+    fn shortest_path(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+    ) -> Option<Vec<(usize, usize)>> {
+        let mut parents: Vec<Option<(usize, usize)>> = vec![None; self.size * self.size];
+        let mut queue = VecDeque::from([start]);
+        parents[start.0 * self.size + start.1] = Some(start);
+
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == goal {
+                let mut path = Vec::new();
+                let mut cell = goal;
+                while cell != start {
+                    path.push(cell);
+                    cell = parents[cell.0 * self.size + cell.1]?;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for r in x.saturating_sub(1)..(x + 2).min(self.size) {
+                for c in y.saturating_sub(1)..(y + 2).min(self.size) {
+                    let index = r * self.size + c;
+                    if parents[index].is_none() && self.is_free(r, c) {
+                        parents[index] = Some((x, y));
+                        queue.push_back((r, c));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Creates a path along the given cells, its cost being the sum of their values.
+    fn path(grid: &Grid, steps: Vec<(usize, usize)>) -> Path {
+        let total_cost = steps
+            .iter()
+            .map(|&(x, y)| grid.value_at(x, y) as usize)
+            .sum();
+        Path {
+            steps: steps.into(),
+            total_cost,
+        }
+    }
+}
+
+impl Planner for Coverage {
+    /// Returns the route of the drone starting at `start`, if there is one.
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        let route = self
+            .routes
+            .iter()
+            .find(|route| route.first() == Some(&start))?;
+        (route.len() > 1).then(|| Self::path(grid, route.clone()))
+    }
+
+    /// Returns the rest of the route of the drone.
+    ///
+    /// Drones that left their route, for example to charge, first fly back to where they left
+    /// it. Drones without a route, such as drones that joined mid-run, are not planned.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` with the rest of the route, or `None` once the route is finished.
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        let route = self.routes.get(drone.id)?;
+        let mut progress = self.progress.lock().unwrap();
+        let done = progress.get_mut(drone.id)?;
+
+        let steps = match route[*done..]
+            .iter()
+            .position(|&cell| cell == drone.position)
+        {
+            Some(offset) => {
+                *done += offset;
+                route[*done..].to_vec()
+            }
+            None => {
+                let mut steps = vec![drone.position];
+                self.extend_to(&mut steps, route[*done]);
+                steps.extend_from_slice(&route[*done + 1..]);
+                steps
+            }
+        };
+        drop(progress);
+
+        (steps.len() > 1).then(|| Self::path(grid, steps))
+    }
+
+    /// Returns `true`, as drones keep their progress along their routes between steps.
+    fn keeps_state(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the lanes sweeping a region, as (column, (first row, last row)).
+///
+/// Every lane covers `swath` columns, the lanes are placed so that together they cover every
+/// column of the region.
+fn lanes(region: &Region, swath: usize) -> Vec<(usize, (usize, usize))> {
+    let width = region.rows.len();
+    let before = (swath - 1) / 2;
+    let after = swath - 1 - before;
+
+    let mut lanes = Vec::new();
+    let mut lane = before.min(width - 1);
+    loop {
+        lanes.push((region.first_col + lane, region.rows[lane]));
+        if lane + after >= width - 1 {
+            return lanes;
+        }
+        lane = (lane + swath).min(width - 1);
+    }
+}
+
+/// Returns whether two ranges of rows overlap.
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// Returns the number of moves between two cells when moving in 8 directions.
+fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the drones together sweep every free cell around an obstacle.
+    #[test]
+    fn test_coverage_around_obstacle() {
+        let obstacle = Obstacle {
+            x: 3,
+            y: 3,
+            height: 4,
+            width: 4,
+        };
+        let coverage = Coverage::new(10, &[obstacle], 1, &[(0, 0), (9, 9)]);
+
+        assert_eq!(
+            coverage.decompose().len(),
+            4,
+            "The obstacle splits the grid into left, top, bottom and right regions"
+        );
+
+        let routes = coverage.routes();
+        for route in routes {
+            for pair in route.windows(2) {
+                assert!(
+                    distance(pair[0], pair[1]) == 1,
+                    "Routes move one cell at a time"
+                );
+            }
+            assert!(route.iter().all(|&(x, y)| !obstacle.contains(x, y)));
+        }
+        for x in 0..10 {
+            for y in 0..10 {
+                let covered = routes.iter().any(|route| route.contains(&(x, y)));
+                assert_eq!(covered, !obstacle.contains(x, y), "Cell ({}, {})", x, y);
+            }
+        }
+
+        let grid = Grid::new(vec![10; 100]);
+        let drone = DroneState {
+            id: 1,
+            ..DroneState::new((9, 9))
+        };
+        let path = coverage
+            .plan(&grid, &drone)
+            .expect("Drone should have a route");
+        assert_eq!(path.steps.front(), Some(&(9, 9)));
+        assert_eq!(path.steps.len(), routes[1].len());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::sensing::BeliefMap;
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Budget;
+use bresenham::Bresenham;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The frontier cells claimed by drones, shared by all frontier planners of a fleet.
+#[derive(Default)]
+pub struct Claims {
+    /// The claimed frontier of every drone, by drone id.
+    frontiers: Mutex<HashMap<usize, (usize, usize)>>,
+}
+
+impl Claims {
+    /// Returns the frontier claimed by a drone, if any.
+    pub fn claim_of(&self, drone: usize) -> Option<(usize, usize)> {
+        self.frontiers.lock().unwrap().get(&drone).copied()
+    }
+}
+
+/// A planner that explores unknown grids by flying to frontiers between known and unknown cells.
+///
+/// Each frontier is scored by its expected reward, its known value plus the prior of its unknown
+/// neighbours, divided by the distance to it. Drones claim the frontier they fly to, and no
+/// drone picks a frontier within `separation` cells of a frontier claimed by another drone.
+/// Claims are chosen and recorded under a single lock, and resolved in drone id order when the
+/// fleet assigns them before planning, see `Planner::assign`. Once no frontiers are left, or for
+/// drones without a belief map, the inner planner takes over.
+pub struct Frontier<P> {
+    /// The planner used once the grid is explored.
+    pub inner: P,
+    /// The minimal distance, in cells along either axis, between frontiers of different drones.
+    pub separation: usize,
+    /// The frontiers claimed by the drones of the fleet.
+    pub claims: Arc<Claims>,
+}
+
+impl<P: Planner> Frontier<P> {
+    /// Returns whether a known cell borders an unknown one.
+    fn is_frontier(belief: &BeliefMap, row: usize, col: usize) -> bool {
+        belief.is_known(row, col) && Self::unknown_neighbours(belief, row, col) > 0
+    }
+
+    /// Returns the number of unknown cells among the 8 neighbours of a cell.
+    fn unknown_neighbours(belief: &BeliefMap, row: usize, col: usize) -> usize {
+        let size = belief.grid.size;
+        let mut unknown = 0;
+        for r in row.saturating_sub(1)..(row + 2).min(size) {
+            for c in col.saturating_sub(1)..(col + 2).min(size) {
+                if (r, c) != (row, col) && !belief.is_known(r, c) {
+                    unknown += 1;
+                }
+            }
+        }
+        unknown
+    }
+
+    /// Keeps the claimed frontier of a drone while it is one, or claims a new one.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which the drone plans, as believed by the drone.
+    /// * `belief` - The belief map of the drone.
+    /// * `drone` - The current state of the drone.
+    ///
+    /// # Returns
+    ///
+    /// The frontier claimed by the drone, or `None` if no frontier is left for it.
+    fn claim(&self, grid: &Grid, belief: &BeliefMap, drone: &DroneState) -> Option<(usize, usize)> {
+        let mut claims = self.claims.frontiers.lock().unwrap();
+        let frontier = match claims.get(&drone.id).copied() {
+            Some(claim)
+                if claim != drone.position && Self::is_frontier(belief, claim.0, claim.1) =>
+            {
+                Some(claim)
+            }
+            _ => self.select(grid, belief, drone, &claims),
+        };
+
+        match frontier {
+            Some(frontier) => claims.insert(drone.id, frontier),
+            None => claims.remove(&drone.id),
+        };
+        frontier
+    }
+
+    /// Selects the best frontier that is not too close to a frontier of another drone.
+    fn select(
+        &self,
+        grid: &Grid,
+        belief: &BeliefMap,
+        drone: &DroneState,
+        claims: &HashMap<usize, (usize, usize)>,
+    ) -> Option<(usize, usize)> {
+        let taken: Vec<(usize, usize)> = claims
+            .iter()
+            .filter(|(&other, _)| other != drone.id)
+            .map(|(_, &frontier)| frontier)
+            .collect();
+        let (x, y) = drone.position;
+
+        let mut best: Option<((usize, usize), f32)> = None;
+        for row in 0..grid.size {
+            for col in 0..grid.size {
+                if (row, col) == drone.position || !Self::is_frontier(belief, row, col) {
+                    continue;
+                }
+                let too_close = taken
+                    .iter()
+                    .any(|&(r, c)| r.abs_diff(row).max(c.abs_diff(col)) <= self.separation);
+                if too_close {
+                    continue;
+                }
+
+                let expected = grid.value_at(row, col) as f32
+                    + (belief.prior() as usize * Self::unknown_neighbours(belief, row, col)) as f32;
+                let distance = x.abs_diff(row).max(y.abs_diff(col)) as f32;
+                let score = expected / (1. + distance);
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some(((row, col), score));
+                }
+            }
+        }
+
+        best.map(|(frontier, _)| frontier)
+    }
+}
+
+impl<P: Planner> Planner for Frontier<P> {
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.inner.solve(grid, start)
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.plan_within(grid, drone, &Budget::unlimited())
+    }
+
+    /// Plans a straight path to the claimed frontier of the drone.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path, as believed by the drone.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the inner planner may take.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        let Some(belief) = &drone.belief else {
+            return self.inner.plan_within(grid, drone, budget);
+        };
+
+        // Keep flying to the claimed frontier while it is one
+        let Some(frontier) = self.claim(grid, belief, drone) else {
+            return self.inner.plan_within(grid, drone, budget);
+        };
+
+        let start = (drone.position.0 as isize, drone.position.1 as isize);
+        let goal = (frontier.0 as isize, frontier.1 as isize);
+        let mut steps: Vec<(usize, usize)> = Bresenham::new(start, goal)
+            .map(|(x, y)| (x as usize, y as usize))
+            .collect();
+        steps.push(frontier);
+
+        let total_cost = steps
+            .iter()
+            .map(|&(x, y)| grid.value_at(x, y) as usize)
+            .sum();
+        Some(Path {
+            steps: steps.into(),
+            total_cost,
+        })
+    }
+
+    /// Claims the frontier of the drone, so drones planning in parallel do not race for one.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid the drone is about to plan on, as believed by the drone.
+    /// * `drone` - The current state of the drone.
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        let claimed = drone
+            .belief
+            .as_ref()
+            .and_then(|belief| self.claim(grid, belief, drone));
+        if claimed.is_none() {
+            self.inner.assign(grid, drone);
+        }
+    }
+
+    /// Frees the frontier claimed by the drone.
+    ///
+    /// # Arguments
+    ///
+    /// * `drone` - The id of the drone that left.
+    fn release(&self, drone: usize) {
+        self.claims.frontiers.lock().unwrap().remove(&drone);
+        self.inner.release(drone);
+    }
+
+    /// Returns `true`, as drones keep their claimed frontiers between steps.
+    fn keeps_state(&self) -> bool {
+        true
+    }
+
+    fn depends_on_time(&self) -> bool {
+        self.inner.depends_on_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::RayCasting;
+    use crate::sensing::Sensor;
+    use crate::utils::Rng;
+
+    /// Tests that two drones next to each other head for different frontiers.
+    #[test]
+    fn test_frontier_claims() {
+        let truth = Grid::load("GRID_S").expect("Could not load grid");
+        let sensor = Sensor {
+            radius: 1,
+            noise: 0,
+            max_age: None,
+            prior: 10,
+        };
+        let planner = Frontier {
+            inner: RayCasting::new(5, 8),
+            separation: 2,
+            claims: Arc::new(Claims::default()),
+        };
+
+        let mut rng = Rng::new(0);
+        let drones: Vec<DroneState> = [(5, 5), (5, 6)]
+            .into_iter()
+            .enumerate()
+            .map(|(id, position)| {
+                let mut belief = sensor.belief(truth.size);
+                sensor.observe(&mut belief, &truth, (5, 5), 0, &mut rng);
+                sensor.observe(&mut belief, &truth, (5, 6), 0, &mut rng);
+                DroneState {
+                    id,
+                    belief: Some(belief),
+                    ..DroneState::new(position)
+                }
+            })
+            .collect();
+
+        let paths: Vec<Path> = drones
+            .iter()
+            .map(|drone| {
+                let belief = &drone.belief.as_ref().unwrap().grid;
+                planner
+                    .plan(belief, drone)
+                    .expect("Planner should find a path")
+            })
+            .collect();
+
+        let first = planner
+            .claims
+            .claim_of(0)
+            .expect("Drone 0 should claim a frontier");
+        let second = planner
+            .claims
+            .claim_of(1)
+            .expect("Drone 1 should claim a frontier");
+        assert!(first.0.abs_diff(second.0).max(first.1.abs_diff(second.1)) > 2);
+        assert_eq!(paths[0].steps.back(), Some(&first));
+        assert_eq!(paths[1].steps.front(), Some(&(5, 6)));
+
+        planner.release(0);
+        assert_eq!(
+            planner.claims.claim_of(0),
+            None,
+            "The claim should be released"
+        );
+        assert_eq!(planner.claims.claim_of(1), Some(second));
+    }
+}
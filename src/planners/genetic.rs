@@ -0,0 +1,164 @@
+use crate::planners::moves;
+use crate::traits::{Planner, Simulator};
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::{Budget, Deadline, Rng};
+use std::time::Duration;
+
+/// The number of sequences competing for every parent.
+const TOURNAMENT: usize = 3;
+
+/// A planner evolving sequences of moves with a genetic algorithm.
+///
+/// Every sequence is valued by simulating the drone flying it, so regrowth is accounted for.
+/// Every generation keeps the best sequence and breeds the others from parents picked by
+/// tournament, with one point crossover and random mutations. The search is anytime: it stops
+/// when the generations or the time budget run out and returns the best sequence so far.
+pub struct Genetic<S> {
+    /// The simulator predicting the outcome of a sequence.
+    pub simulator: S,
+    /// The number of moves of a sequence.
+    pub horizon: usize,
+    /// The number of sequences of a generation.
+    pub population: usize,
+    /// The maximum number of generations.
+    pub generations: usize,
+    /// The probability that a move of a child is replaced by a random one.
+    pub mutation_rate: f32,
+    /// The time budget of a single call in ms.
+    pub budget: usize,
+    /// The seed of the searches, mixed with the drone and grid of every call, see `moves::seed`.
+    pub seed: u64,
+}
+
+impl<S: Simulator> Genetic<S> {
+    /// Picks the best of a few random sequences.
+    fn tournament<'a>(population: &'a [(Vec<u8>, usize)], rng: &mut Rng) -> &'a [u8] {
+        (0..TOURNAMENT)
+            .map(|_| &population[rng.below(population.len())])
+            .max_by_key(|(_, harvested)| *harvested)
+            .map(|(moves, _)| moves.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+impl<S: Simulator> Planner for Genetic<S> {
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.plan(grid, &DroneState::new(start))
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.plan_within(grid, drone, &Budget::unlimited())
+    }
+
+    /// Evolves the sequence of moves harvesting the most within the horizon.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the call may take, on top of the budget of the planner.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` with the best sequence found, its cost being the value harvested, or
+    /// `None` if it does not move.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        let time = budget.cap(Duration::from_millis(self.budget as u64));
+        let mut deadline = Deadline::new(time.as_secs_f32() * 1000.);
+        let mut rng = Rng::new(moves::seed(self.seed, grid, drone));
+        let fitness = |sequence: &[u8]| {
+            let cells = moves::decode(drone.position, sequence, grid.size);
+            moves::evaluate(&self.simulator, grid, drone, &cells)
+        };
+
+        // Start with a smaller population rather than overrunning the budget
+        let mut population: Vec<(Vec<u8>, usize)> = Vec::with_capacity(self.population.max(2));
+        while population.len() < self.population.max(2) {
+            if population.len() >= 2 && deadline.is_expired() {
+                break;
+            }
+            let sequence = moves::random(&mut rng, self.horizon);
+            let harvested = fitness(&sequence);
+            population.push((sequence, harvested));
+        }
+
+        for _ in 0..self.generations {
+            population.sort_by_key(|(_, harvested)| std::cmp::Reverse(*harvested));
+
+            let mut next = vec![population[0].clone()];
+            while next.len() < population.len() {
+                let first = Self::tournament(&population, &mut rng);
+                let second = Self::tournament(&population, &mut rng);
+                let cut = rng.below(self.horizon + 1);
+                let mut child: Vec<u8> =
+                    first[..cut].iter().chain(&second[cut..]).copied().collect();
+                for gene in child.iter_mut() {
+                    if rng.next_f32() < self.mutation_rate {
+                        *gene = rng.below(moves::MOVES.len()) as u8;
+                    }
+                }
+                let harvested = fitness(&child);
+                next.push((child, harvested));
+            }
+            population = next;
+
+            deadline.tick();
+            if deadline.will_exceed_deadline() || budget.is_aborted() {
+                log::debug!("Genetic planner out of time");
+                break;
+            }
+        }
+
+        let (best, harvested) = population
+            .into_iter()
+            .max_by_key(|(_, harvested)| *harvested)?;
+        moves::path(moves::decode(drone.position, &best, grid.size), harvested)
+    }
+
+    /// Returns `true`, as the search stops when its time budget runs out.
+    fn depends_on_time(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulators::Incremental;
+
+    /// Tests that evolved sequences are valid moves and beat a straight line.
+    #[test]
+    fn test_genetic_plan() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let simulator = Incremental {
+            increment_step: 1,
+            start_grid: grid.clone(),
+        };
+        let mut planner = Genetic {
+            simulator,
+            horizon: 8,
+            population: 20,
+            generations: 30,
+            mutation_rate: 0.1,
+            budget: 10_000,
+            seed: 0,
+        };
+        let drone = DroneState::new((5, 5));
+
+        let path = planner.plan(&grid, &drone).expect("Planner should move");
+        let cells: Vec<(usize, usize)> = path.steps.iter().copied().collect();
+        assert!(cells.len() <= 9);
+        assert_eq!(
+            path.total_cost,
+            moves::evaluate(&planner.simulator, &grid, &drone, &cells)
+        );
+        let straight = moves::decode((5, 5), &[0; 8], grid.size);
+        assert!(path.total_cost >= moves::evaluate(&planner.simulator, &grid, &drone, &straight));
+
+        planner.budget = 0;
+        assert!(
+            planner.plan(&grid, &drone).is_some(),
+            "Best sequence so far is returned when out of time"
+        );
+    }
+}
@@ -1,4 +1,19 @@
+pub mod annealing;
 pub mod bfs;
+pub mod coverage;
+pub mod frontier;
+pub mod genetic;
+pub mod moves;
+pub mod orienteering;
+pub mod profiled;
 pub mod ray_casting;
+pub mod return_to_base;
+pub use annealing::Annealing;
 pub use bfs::BFS;
+pub use coverage::Coverage;
+pub use frontier::{Claims, Frontier};
+pub use genetic::Genetic;
+pub use orienteering::{Orienteering, Tours};
+pub use profiled::Profiled;
 pub use ray_casting::RayCasting;
+pub use return_to_base::ReturnToBase;
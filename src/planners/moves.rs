@@ -0,0 +1,118 @@
+use crate::traits::Simulator;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Rng;
+use std::hash::{Hash, Hasher};
+
+/// The 8 moves to the neighbouring cells, as (dx, dy).
+pub const MOVES: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// A 64-bit FNV-1a hasher, whose output does not change between Rust releases.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+/// Returns the seed of a search for a drone, derived from the seed of the planner.
+///
+/// The id and position of the drone and the grid it plans on are mixed in, so searches are
+/// reproducible but differ between drones and between steps.
+pub fn seed(seed: u64, grid: &Grid, drone: &DroneState) -> u64 {
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    (seed, drone.id, drone.position, &grid.data).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns a random sequence of moves, as indices into [`MOVES`].
+pub fn random(rng: &mut Rng, horizon: usize) -> Vec<u8> {
+    (0..horizon).map(|_| rng.below(MOVES.len()) as u8).collect()
+}
+
+/// Returns the cells visited by a sequence of moves from a start cell.
+///
+/// Moves that would leave the grid are skipped.
+pub fn decode(start: (usize, usize), moves: &[u8], size: usize) -> Vec<(usize, usize)> {
+    let mut cells = vec![start];
+    let (mut x, mut y) = start;
+    for &index in moves {
+        let (dx, dy) = MOVES[index as usize];
+        let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+            continue;
+        };
+        if nx < size && ny < size {
+            (x, y) = (nx, ny);
+            cells.push((x, y));
+        }
+    }
+    cells
+}
+
+/// Simulates a drone flying over the cells and returns the total value it harvests.
+///
+/// The value harvested in a step is the harvest the simulator reports for the drone, see
+/// `DroneState::harvested`. The simulation stops at the first error.
+///
+/// # Arguments
+///
+/// * `simulator` - The simulator predicting the grid.
+/// * `grid` - The current state of the grid.
+/// * `drone` - The state of the drone, its position being the first cell.
+/// * `cells` - The cells to fly over.
+pub fn evaluate(
+    simulator: &impl Simulator,
+    grid: &Grid,
+    drone: &DroneState,
+    cells: &[(usize, usize)],
+) -> usize {
+    let mut grid = grid.clone();
+    let mut drone = drone.clone();
+    let mut harvested = 0;
+    let mut at = 0;
+
+    while at + 1 < cells.len() {
+        let path = Path {
+            steps: cells[at..].iter().copied().collect(),
+            total_cost: 0,
+        };
+        let Ok(next) = simulator.advance(&grid, &mut drone, &path) else {
+            break;
+        };
+        harvested += drone.harvested;
+        grid = next;
+
+        match cells[at + 1..]
+            .iter()
+            .position(|&cell| cell == drone.position)
+        {
+            Some(offset) => at += offset + 1,
+            None => break,
+        }
+    }
+
+    harvested
+}
+
+/// Creates a path over the cells, if it moves at all.
+pub fn path(cells: Vec<(usize, usize)>, harvested: usize) -> Option<Path> {
+    (cells.len() > 1).then(|| Path {
+        steps: cells.into(),
+        total_cost: harvested,
+    })
+}
@@ -0,0 +1,458 @@
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::{Budget, Rng};
+use bresenham::Bresenham;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// The number of most rewarding cells considered as waypoints.
+const MAX_CANDIDATES: usize = 256;
+
+/// The number of best insertions a random one is picked from while constructing a tour.
+const CANDIDATE_LIST: usize = 3;
+
+/// A planned tour, with the index of the cell the drone last planned from.
+type Progress = (Vec<(usize, usize)>, usize);
+
+/// The tours planned by drones, shared by all orienteering planners of a fleet.
+#[derive(Default)]
+pub struct Tours {
+    /// The tour of every drone, by drone id.
+    tours: Mutex<HashMap<usize, Progress>>,
+}
+
+impl Tours {
+    /// Returns the tour planned by a drone, if any.
+    pub fn tour_of(&self, drone: usize) -> Option<Vec<(usize, usize)>> {
+        self.tours
+            .lock()
+            .unwrap()
+            .get(&drone)
+            .map(|(tour, _)| tour.clone())
+    }
+}
+
+/// An offline planner solving the orienteering problem over the grid.
+///
+/// It searches the tour of at most `budget` moves that collects the most reward, counting every
+/// cell once and ignoring regrowth. Tours are built by greedy randomised insertion (GRASP) of
+/// waypoints and improved by iterated local search, which repeatedly drops part of the best
+/// tour, shortens it with 2-opt and refills the freed budget.
+///
+/// As a planner, every drone plans its tour once, without the cells already on the tours of
+/// other drones, and then follows it. Tours are planned when the fleet assigns them, one drone
+/// at a time in drone id order, see `Planner::assign`.
+pub struct Orienteering {
+    /// The maximum number of moves of a tour.
+    pub budget: usize,
+    /// The cell tours have to end at, if any.
+    pub end: Option<(usize, usize)>,
+    /// The number of local search iterations.
+    pub iterations: usize,
+    /// The seed of the randomised search.
+    pub seed: u64,
+    /// The tours of the drones of the fleet that planned one.
+    pub tours: Arc<Tours>,
+}
+
+impl Orienteering {
+    /// Creates the planner without any planned tours.
+    pub fn new(budget: usize, end: Option<(usize, usize)>, iterations: usize, seed: u64) -> Self {
+        Orienteering {
+            budget,
+            end,
+            iterations,
+            seed,
+            tours: Arc::new(Tours::default()),
+        }
+    }
+
+    /// Finds a tour collecting as much reward as possible within the budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The rewards of the cells.
+    /// * `start` - The cell the tour starts at.
+    ///
+    /// # Returns
+    ///
+    /// The cells of the tour, starting at `start`, or `None` if the end is out of reach.
+    pub fn tour(&self, grid: &Grid, start: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        self.tour_within(grid, start, &Budget::unlimited())
+    }
+
+    /// Finds a tour like [`Orienteering::tour`], stopping the local search when the budget expires.
+    pub fn tour_within(
+        &self,
+        grid: &Grid,
+        start: (usize, usize),
+        budget: &Budget,
+    ) -> Option<Vec<(usize, usize)>> {
+        if self
+            .end
+            .is_some_and(|end| distance(start, end) > self.budget)
+        {
+            return None;
+        }
+
+        let mut candidates: Vec<(usize, usize)> = (0..grid.size * grid.size)
+            .map(|index| (index / grid.size, index % grid.size))
+            .filter(|&cell| cell != start && Some(cell) != self.end)
+            .filter(|&(x, y)| grid.value_at(x, y) > 0)
+            .filter(|&cell| self.reachable(start, cell))
+            .collect();
+        candidates.sort_by_key(|&(x, y)| std::cmp::Reverse(grid.value_at(x, y)));
+        candidates.truncate(MAX_CANDIDATES);
+
+        let mut rng = Rng::new(self.seed);
+        let mut best = Vec::new();
+        self.construct(grid, start, &candidates, &mut best, &mut rng);
+        let mut best_reward = reward(grid, &self.expand(start, &best));
+
+        for _ in 0..self.iterations {
+            if budget.is_expired() {
+                log::debug!("Orienteering planner out of time");
+                break;
+            }
+            let mut waypoints = best.clone();
+            if !waypoints.is_empty() {
+                let len = 1 + rng.below(waypoints.len().div_ceil(3));
+                let at = rng.below(waypoints.len() - len + 1);
+                waypoints.drain(at..at + len);
+            }
+            self.construct(grid, start, &candidates, &mut waypoints, &mut rng);
+            self.two_opt(start, &mut waypoints);
+            self.construct(grid, start, &candidates, &mut waypoints, &mut rng);
+
+            let candidate_reward = reward(grid, &self.expand(start, &waypoints));
+            if candidate_reward > best_reward {
+                best = waypoints;
+                best_reward = candidate_reward;
+            }
+        }
+
+        Some(self.expand(start, &best))
+    }
+
+    /// Returns whether a cell can be visited within the budget.
+    fn reachable(&self, start: (usize, usize), cell: (usize, usize)) -> bool {
+        let back = self.end.map_or(0, |end| distance(cell, end));
+        distance(start, cell) + back <= self.budget
+    }
+
+    /// Returns the number of moves of a tour through the waypoints.
+    fn length(&self, start: (usize, usize), waypoints: &[(usize, usize)]) -> usize {
+        let mut length = 0;
+        let mut previous = start;
+        for &cell in waypoints.iter().chain(self.end.as_ref()) {
+            length += distance(previous, cell);
+            previous = cell;
+        }
+        length
+    }
+
+    /// Inserts waypoints until none fits the budget anymore.
+    ///
+    /// Every waypoint is inserted where it lengthens the tour least. The next waypoint is picked
+    /// at random among the few with the most reward per added move.
+    fn construct(
+        &self,
+        grid: &Grid,
+        start: (usize, usize),
+        candidates: &[(usize, usize)],
+        waypoints: &mut Vec<(usize, usize)>,
+        rng: &mut Rng,
+    ) {
+        let mut length = self.length(start, waypoints);
+        loop {
+            let visited: HashSet<(usize, usize)> = waypoints.iter().copied().collect();
+            let mut insertions: Vec<(f32, (usize, usize), usize, usize)> = candidates
+                .iter()
+                .filter(|cell| !visited.contains(cell))
+                .filter_map(|&cell| {
+                    let (at, added) = self.cheapest_insertion(start, waypoints, cell);
+                    let value = grid.value_at(cell.0, cell.1) as f32;
+                    (length + added <= self.budget).then_some((
+                        value / (added + 1) as f32,
+                        cell,
+                        at,
+                        added,
+                    ))
+                })
+                .collect();
+            if insertions.is_empty() {
+                return;
+            }
+
+            insertions.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            let (_, cell, at, added) = insertions[rng.below(insertions.len().min(CANDIDATE_LIST))];
+            waypoints.insert(at, cell);
+            length += added;
+        }
+    }
+
+    /// Returns where a cell lengthens the tour least, and by how many moves.
+    fn cheapest_insertion(
+        &self,
+        start: (usize, usize),
+        waypoints: &[(usize, usize)],
+        cell: (usize, usize),
+    ) -> (usize, usize) {
+        (0..=waypoints.len())
+            .map(|at| {
+                let previous = if at == 0 { start } else { waypoints[at - 1] };
+                let added = match waypoints.get(at).or(self.end.as_ref()) {
+                    Some(&next) => {
+                        distance(previous, cell) + distance(cell, next) - distance(previous, next)
+                    }
+                    None => distance(previous, cell),
+                };
+                (at, added)
+            })
+            .min_by_key(|&(_, added)| added)
+            .unwrap_or((0, distance(start, cell)))
+    }
+
+    /// Shortens the tour by reversing segments of waypoints for as long as that helps.
+    fn two_opt(&self, start: (usize, usize), waypoints: &mut [(usize, usize)]) {
+        let mut length = self.length(start, waypoints);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for i in 0..waypoints.len() {
+                for j in (i + 1)..waypoints.len() {
+                    waypoints[i..=j].reverse();
+                    let shorter = self.length(start, waypoints);
+                    if shorter < length {
+                        length = shorter;
+                        improved = true;
+                    } else {
+                        waypoints[i..=j].reverse();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expands the waypoints of a tour into the cells flown over, moving in 8 directions.
+    fn expand(&self, start: (usize, usize), waypoints: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut cells = vec![start];
+        for &goal in waypoints.iter().chain(self.end.as_ref()) {
+            let from = cells[cells.len() - 1];
+            let line = Bresenham::new(
+                (from.0 as isize, from.1 as isize),
+                (goal.0 as isize, goal.1 as isize),
+            );
+            cells.extend(line.skip(1).map(|(x, y)| (x as usize, y as usize)));
+            if from != goal {
+                cells.push(goal);
+            }
+        }
+        cells
+    }
+
+    /// Plans the tour of the drone if it has none yet, leaving out the cells on the tours of
+    /// other drones. The lock is not held while the tour is searched.
+    ///
+    /// # Returns
+    ///
+    /// `Some(())` if the drone has a tour, or `None` if no tour is possible.
+    fn start_tour(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<()> {
+        let mut remaining = grid.clone();
+        {
+            let tours = self.tours.tours.lock().unwrap();
+            if tours.contains_key(&drone.id) {
+                return Some(());
+            }
+            // Leave the cells other drones will collect out of the tour
+            for (tour, _) in tours.values() {
+                for &(x, y) in tour {
+                    remaining.data[x * grid.size + y] = 0;
+                }
+            }
+        }
+        let tour = self.tour_within(&remaining, drone.position, budget)?;
+        self.tours
+            .tours
+            .lock()
+            .unwrap()
+            .entry(drone.id)
+            .or_insert((tour, 0));
+        Some(())
+    }
+}
+
+impl Planner for Orienteering {
+    /// Solves the orienteering problem for a whole tour from `start`.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `start` - The starting position (x, y) on the grid.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` with the whole tour, its cost being the reward collected, or `None`
+    /// if no move is possible within the budget.
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        let cells = self.tour(grid, start)?;
+        (cells.len() > 1).then(|| Path {
+            total_cost: reward(grid, &cells),
+            steps: cells.into(),
+        })
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.plan_within(grid, drone, &Budget::unlimited())
+    }
+
+    /// Returns the rest of the tour of the drone, planning it first if it has none yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time planning a missing tour may take.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` with the rest of the tour, or `None` once the tour is finished.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        self.start_tour(grid, drone, budget)?;
+        let mut tours = self.tours.tours.lock().unwrap();
+        let (tour, done) = tours.get_mut(&drone.id)?;
+        let steps: Vec<(usize, usize)> = match tour[*done..]
+            .iter()
+            .position(|&cell| cell == drone.position)
+        {
+            Some(offset) => {
+                *done += offset;
+                tour[*done..].to_vec()
+            }
+            None => {
+                // Fly back to where the drone left its tour
+                let mut steps = self.expand(drone.position, &[tour[*done]]);
+                steps.extend_from_slice(&tour[*done + 1..]);
+                steps
+            }
+        };
+        drop(tours);
+
+        (steps.len() > 1).then(|| Path {
+            total_cost: reward(grid, &steps),
+            steps: steps.into(),
+        })
+    }
+
+    /// Plans the tour of the drone if it has none yet, so tours are built in drone id order.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid the drone is about to plan on.
+    /// * `drone` - The current state of the drone.
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        self.start_tour(grid, drone, &Budget::unlimited());
+    }
+
+    /// Drops the tour of the drone, so its cells are left to the tours planned afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `drone` - The id of the drone that left.
+    fn release(&self, drone: usize) {
+        self.tours.tours.lock().unwrap().remove(&drone);
+    }
+
+    /// Returns `true`, as drones keep following the tour they planned on their first call.
+    fn keeps_state(&self) -> bool {
+        true
+    }
+}
+
+/// Logs how much of the reward of an orienteering tour of the same length every drone collected.
+///
+/// Both the flown traces and the reference tours are valued on the starting grid, counting every
+/// cell once, so runs of other planners can be compared against a near optimal tour.
+///
+/// # Arguments
+///
+/// * `grid` - The starting grid.
+/// * `traces` - The path trace of every drone.
+/// * `iterations` - The number of local search iterations of the reference tours.
+/// * `seed` - The seed of the reference search.
+pub fn log_reference(grid: &Grid, traces: &[Vec<Path>], iterations: usize, seed: u64) {
+    for (drone, trace) in traces.iter().enumerate() {
+        let Some(trace) = trace.first() else {
+            continue;
+        };
+        let Some(&start) = trace.steps.front() else {
+            continue;
+        };
+        let cells: Vec<(usize, usize)> = trace.steps.iter().copied().collect();
+        let reference = Orienteering::new(cells.len() - 1, None, iterations, seed)
+            .tour(grid, start)
+            .map_or(0, |tour| reward(grid, &tour));
+        let collected = reward(grid, &cells);
+
+        log::info!(
+            "Drone {} collected {} of the {} of the reference tour ({:.1}%)",
+            drone,
+            collected,
+            reference,
+            100. * collected as f32 / reference.max(1) as f32
+        );
+    }
+}
+
+/// Returns the reward of flying over the cells, counting every cell once.
+///
+/// # Arguments
+///
+/// * `grid` - The rewards of the cells.
+/// * `cells` - The cells flown over.
+pub fn reward(grid: &Grid, cells: &[(usize, usize)]) -> usize {
+    let visited: HashSet<&(usize, usize)> = cells.iter().collect();
+    visited
+        .into_iter()
+        .map(|&(x, y)| grid.value_at(x, y) as usize)
+        .sum()
+}
+
+/// Returns the number of moves between two cells when moving in 8 directions.
+fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that tours respect the budget and end and collect at least as much as a straight line.
+    #[test]
+    fn test_orienteering_tour() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let planner = Orienteering::new(15, Some((0, 0)), 30, 0);
+
+        let tour = planner.tour(&grid, (0, 0)).expect("End is within reach");
+        assert_eq!(tour.first(), Some(&(0, 0)));
+        assert_eq!(tour.last(), Some(&(0, 0)));
+        assert!(tour.len() - 1 <= 15, "Tour exceeds the budget");
+        for pair in tour.windows(2) {
+            assert_eq!(
+                distance(pair[0], pair[1]),
+                1,
+                "Tours move one cell at a time"
+            );
+        }
+
+        let open = Orienteering::new(15, None, 30, 0);
+        let straight: Vec<(usize, usize)> = (0..16).map(|i| (i, i)).collect();
+        let open_tour = open.tour(&grid, (0, 0)).unwrap();
+        assert!(reward(&grid, &open_tour) >= reward(&grid, &straight));
+        assert!(reward(&grid, &open_tour) >= reward(&grid, &tour));
+
+        assert!(Orienteering::new(3, Some((10, 10)), 1, 0)
+            .tour(&grid, (0, 0))
+            .is_none());
+    }
+}
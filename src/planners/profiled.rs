@@ -0,0 +1,59 @@
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Budget;
+
+/// A planner that plans every drone with the planner of its profile.
+pub struct Profiled {
+    /// The planner of every profile, indexed by the profile of the drone.
+    pub planners: Vec<Box<dyn Planner + Send + Sync>>,
+}
+
+impl Planner for Profiled {
+    /// Solves with the planner of the first profile, as there is no drone to select one by.
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.planners.first()?.solve(grid, start)
+    }
+
+    /// Solves with the planner of the profile of the drone.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if the drone has no known profile or
+    /// no path is possible.
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.planners.get(drone.profile)?.plan(grid, drone)
+    }
+
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        self.planners
+            .get(drone.profile)?
+            .plan_within(grid, drone, budget)
+    }
+
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        if let Some(planner) = self.planners.get(drone.profile) {
+            planner.assign(grid, drone);
+        }
+    }
+
+    fn release(&self, drone: usize) {
+        for planner in &self.planners {
+            planner.release(drone);
+        }
+    }
+
+    fn keeps_state(&self) -> bool {
+        self.planners.iter().any(|planner| planner.keeps_state())
+    }
+
+    fn depends_on_time(&self) -> bool {
+        self.planners
+            .iter()
+            .any(|planner| planner.depends_on_time())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::traits::Planner;
-use crate::types::{Grid, Path};
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Budget;
 use bresenham::Bresenham;
 use std::f32;
 use std::f32::consts;
@@ -10,20 +11,39 @@ pub struct RayCasting {
     pub len: usize,
     /// The number of rays to cast.
     pub rays: usize,
+    /// The number of empty cells added to every ray when averaging its value, which favours long
+    /// rays over rays cut short by the edge of the map.
+    pub edge_bias: f32,
+    /// The weight of every cell of a ray relative to the previous one, below 1 to favour values
+    /// close to the drone.
+    pub falloff: f32,
 }
 
-impl Planner for RayCasting {
-    /// Solves the path planning problem using ray casting.
-    ///
-    /// # Arguments
-    ///
-    /// * `grid` - The grid on which to plan the path.
-    /// * `start` - The starting position (x, y) on the grid.
-    ///
-    /// # Returns
-    ///
-    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
-    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+impl RayCasting {
+    /// The default edge bias.
+    pub const EDGE_BIAS: f32 = 2.;
+
+    /// The default falloff, weighing all cells of a ray the same.
+    pub const FALLOFF: f32 = 1.;
+
+    /// Creates the planner with the default edge bias and falloff.
+    pub fn new(len: usize, rays: usize) -> Self {
+        RayCasting {
+            len,
+            rays,
+            edge_bias: Self::EDGE_BIAS,
+            falloff: Self::FALLOFF,
+        }
+    }
+
+    /// Sets the edge bias, at least 0 so the average of a ray is never divided by 0 or less.
+    pub fn with_edge_bias(mut self, edge_bias: f32) -> Self {
+        self.edge_bias = edge_bias.max(0.);
+        self
+    }
+
+    /// Casts the rays one by one and keeps the most valuable, stopping once the budget expires.
+    fn cast(&self, grid: &Grid, start: (usize, usize), budget: &Budget) -> Option<Path> {
         let d_angle: f32 = 2.0 * consts::PI / self.rays as f32;
         let mut angles = Vec::<f32>::with_capacity(self.rays);
         let mut goals = Vec::<(isize, isize)>::with_capacity(self.rays);
@@ -53,20 +73,29 @@ impl Planner for RayCasting {
 
         // Evaluate each goal
         for goal in goals {
+            if budget.is_expired() {
+                log::debug!("Budget expired, keeping the best ray cast so far");
+                break;
+            }
             let mut line_points =
                 Bresenham::new((start.0 as isize, start.1 as isize), goal).collect::<Vec<_>>();
             line_points.retain(|&(x, y)| {
                 x >= 0 && y >= 0 && x < grid.size as isize && y < grid.size as isize
             });
             let mut line_value: f32 = 0.0;
+            let mut weighted_value: f32 = 0.0;
+            let mut weight: f32 = 1.0;
 
-            // Calculate total value along the line
+            // Calculate total value along the line, weighted by the distance from the start
             for line_point in &line_points {
-                line_value += grid.value_at(line_point.0 as usize, line_point.1 as usize) as f32;
+                let value = grid.value_at(line_point.0 as usize, line_point.1 as usize) as f32;
+                line_value += value;
+                weighted_value += weight * value;
+                weight *= self.falloff;
             }
 
-            // NOTE: +2 to bias the avg to avoid the map edge
-            let line_avg_value = line_value as f32 / (line_points.len() as f32 + 2.);
+            // NOTE: Bias the avg to avoid the map edge
+            let line_avg_value = weighted_value / (line_points.len() as f32 + self.edge_bias);
 
             let converted_line_points: Vec<(usize, usize)> = line_points
                 .into_iter()
@@ -86,6 +115,37 @@ impl Planner for RayCasting {
     }
 }
 
+impl Planner for RayCasting {
+    /// Solves the path planning problem using ray casting.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `start` - The starting position (x, y) on the grid.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.cast(grid, start, &Budget::unlimited())
+    }
+
+    /// Casts the rays from the position of the drone until the budget expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the call may take.
+    ///
+    /// # Returns
+    ///
+    /// Returns the best path among the rays cast in time, or `None` if no path is possible.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        self.cast(grid, drone.position, budget)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,7 +153,7 @@ mod tests {
     /// Tests the ray casting planner with a small grid.
     #[test]
     fn test_ray_casting_planner() {
-        let planner = RayCasting { len: 3, rays: 8 };
+        let planner = RayCasting::new(3, 8);
 
         let grid = Grid::load("GRID_S").expect("Could not load grid");
 
@@ -108,4 +168,31 @@ mod tests {
             assert!(path.steps.len() <= 3, "Path should not exceed ray len");
         }
     }
+
+    /// Tests that no more rays are cast once the budget expired.
+    #[test]
+    fn test_ray_casting_stops_when_budget_expired() {
+        let planner = RayCasting::new(3, 8);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let drone = DroneState::new((1, 1));
+
+        let expired = Budget::new(std::time::Duration::ZERO);
+        assert!(planner.plan_within(&grid, &drone, &expired).is_none());
+        assert!(planner
+            .plan_within(&grid, &drone, &Budget::unlimited())
+            .is_some());
+    }
+
+    /// Tests that negative edge biases are clamped, so no ray is scored by dividing by 0.
+    #[test]
+    fn test_ray_casting_clamps_edge_bias() {
+        let planner = RayCasting::new(3, 8).with_edge_bias(-3.);
+        assert_eq!(planner.edge_bias, 0.);
+
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let path = planner
+            .solve(&grid, (1, 1))
+            .expect("Planner should find a path");
+        assert!(path.steps.len() > 1);
+    }
 }
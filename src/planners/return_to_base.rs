@@ -0,0 +1,144 @@
+use crate::energy::EnergyModel;
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Budget;
+
+/// A planner that keeps energy constrained drones able to reach a charging station.
+///
+/// The inner planner is followed as long as the drone can still reach the nearest station after
+/// its next move. Otherwise the drone flies straight to the nearest station and stays there
+/// until its battery is full again.
+pub struct ReturnToBase<P> {
+    /// The planner used while the drone has charge to spare.
+    pub inner: P,
+    /// The battery and charging model.
+    pub model: EnergyModel,
+}
+
+impl<P: Planner> ReturnToBase<P> {
+    /// Creates the path back to the nearest charging station, or to stay on the current one.
+    fn return_plan(&self, grid: &Grid, position: (usize, usize)) -> Option<Path> {
+        let (station, _) = self.model.nearest_station(position)?;
+        let mut steps: Vec<(usize, usize)> = self.model.route(position, station);
+        if steps.len() == 1 {
+            steps.push(position);
+        }
+
+        let total_cost = steps
+            .iter()
+            .map(|&(x, y)| grid.value_at(x, y) as usize)
+            .sum();
+        Some(Path {
+            steps: steps.into(),
+            total_cost,
+        })
+    }
+}
+
+impl<P: Planner> Planner for ReturnToBase<P> {
+    fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+        self.inner.solve(grid, start)
+    }
+
+    fn plan(&self, grid: &Grid, drone: &DroneState) -> Option<Path> {
+        self.plan_within(grid, drone, &Budget::unlimited())
+    }
+
+    /// Plans with the inner planner unless the drone has to return to charge.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid on which to plan the path.
+    /// * `drone` - The current state of the drone.
+    /// * `budget` - The time the inner planner may take.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Path)` if a path is found, or `None` if no path is possible.
+    fn plan_within(&self, grid: &Grid, drone: &DroneState, budget: &Budget) -> Option<Path> {
+        let Some(charge) = drone.charge else {
+            return self.inner.plan_within(grid, drone, budget);
+        };
+        let position = drone.position;
+
+        // Charge up before leaving a station
+        if self.model.is_station(position) && charge < self.model.capacity {
+            return self.return_plan(grid, position);
+        }
+
+        // Check the cell the drone reaches this step, given its speed
+        let path = self.inner.plan_within(grid, drone, budget)?;
+        let reach = drone.speed.max(1).min(path.steps.len() - 1);
+        let next = path.steps[reach];
+        let remaining = charge - self.model.path_cost(path.steps.iter().take(reach + 1));
+        if remaining >= self.model.return_cost(next) {
+            return Some(path);
+        }
+
+        log::debug!(
+            "Drone at {:?} returns to charge, {:.1} left",
+            position,
+            charge
+        );
+        self.return_plan(grid, position)
+    }
+
+    fn assign(&self, grid: &Grid, drone: &DroneState) {
+        self.inner.assign(grid, drone)
+    }
+
+    fn release(&self, drone: usize) {
+        self.inner.release(drone)
+    }
+
+    fn keeps_state(&self) -> bool {
+        self.inner.keeps_state()
+    }
+
+    fn depends_on_time(&self) -> bool {
+        self.inner.depends_on_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::RayCasting;
+
+    /// Tests that a drone low on charge heads for the nearest station.
+    #[test]
+    fn test_return_to_base() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let planner = ReturnToBase {
+            inner: RayCasting::new(5, 8),
+            model: EnergyModel {
+                capacity: 100.,
+                straight_cost: 1.,
+                diagonal_cost: 1.5,
+                charge_rate: 10.,
+                stations: vec![(0, 0), (19, 19)],
+            },
+        };
+
+        let full = DroneState {
+            charge: Some(100.),
+            ..DroneState::new((5, 3))
+        };
+        let low = DroneState {
+            charge: Some(4.5),
+            ..full.clone()
+        };
+
+        let path = planner
+            .plan(&grid, &low)
+            .expect("Planner should find a path");
+        assert_eq!(path.steps.front(), Some(&(5, 3)));
+        assert_eq!(path.steps.back(), Some(&(0, 0)), "Drone should head home");
+
+        let path = planner
+            .plan(&grid, &full)
+            .expect("Planner should find a path");
+        let next = path.steps[1];
+        assert!(100. - planner.model.move_cost((5, 3), next) >= planner.model.return_cost(next));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::parsers::Cli;
+use crate::run::fleet::Fleet;
+use crate::types::{Grid, Path};
+use crate::utils::Rng;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// The max duration in ms of a replay, long enough for the deadline never to end it.
+const REPLAY_DURATION: usize = 24 * 60 * 60 * 1000;
+
+/// The full state of a lockstep run at the end of a step.
+///
+/// A checkpoint records the arguments the run was started with, so the run can be rebuilt from
+/// it alone. Resuming continues the run from the checkpoint, replaying re-executes the run from
+/// the start and verifies that it reproduces the recorded paths.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The command line arguments the run was started with, empty for runs not started from the
+    /// command line.
+    pub args: Vec<String>,
+    /// The number of steps completed.
+    pub step: usize,
+    /// The grid at the start of the run, which the simulator recovers towards.
+    pub starting_grid: Grid,
+    /// The grid at the end of the step.
+    pub grid: Grid,
+    /// The drones with their states, paths and lifetimes.
+    pub fleet: Fleet,
+    /// The state of the random number generator.
+    pub rng: Rng,
+}
+
+impl Checkpoint {
+    /// Creates the checkpoint of a run that has not started yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The command line arguments `cli` was parsed from, empty if there are none
+    /// * `cli` - Command line interface arguments
+    /// * `starting_grid` - The initial state of the grid
+    /// * `fleet` - The drones taking part
+    pub fn start(args: Vec<String>, cli: &Cli, starting_grid: &Grid, fleet: Fleet) -> Self {
+        Checkpoint {
+            args,
+            step: 0,
+            starting_grid: starting_grid.clone(),
+            grid: starting_grid.clone(),
+            fleet,
+            rng: Rng::new(cli.seed),
+        }
+    }
+
+    /// Loads a checkpoint from a JSON file.
+    pub fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read checkpoint: {}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid checkpoint: {}", path))
+    }
+
+    /// Saves the checkpoint as a JSON file.
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string(self)?;
+        fs::write(path, text)
+            .with_context(|| format!("Failed to write checkpoint: {}", path.display()))?;
+        log::debug!(
+            "Saved checkpoint of step {} to {}",
+            self.step,
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Saves the checkpoint to the checkpoint directory of the `cli`, if it is due.
+    ///
+    /// Checkpoints are due every `cli.checkpoint_every` steps and at the last step of the run.
+    pub fn save_if_due(&self, cli: &Cli, last: bool) -> Result<()> {
+        let Some(dir) = &cli.checkpoint else {
+            return Ok(());
+        };
+        if last || self.step.is_multiple_of(cli.checkpoint_every.max(1)) {
+            let path = std::path::Path::new(dir).join(format!("step-{}.json", self.step));
+            self.save(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the command line arguments of the recorded run.
+    ///
+    /// The checkpoint, resume and replay options are taken from `current`. A replay runs exactly
+    /// the recorded number of steps and is never ended by the deadline.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The command line arguments of the current process
+    ///
+    /// # Returns
+    ///
+    /// The arguments to run with, or an error if the arguments are missing or invalid
+    pub fn cli(&self, current: &Cli) -> Result<Cli> {
+        if self.args.is_empty() {
+            anyhow::bail!("The checkpoint does not record the arguments of the run");
+        }
+        let mut cli = Cli::try_parse_from(&self.args).context("Invalid recorded arguments")?;
+        cli.checkpoint.clone_from(&current.checkpoint);
+        cli.checkpoint_every = current.checkpoint_every;
+        cli.resume.clone_from(&current.resume);
+        cli.replay.clone_from(&current.replay);
+        if cli.replay.is_some() {
+            cli.time_steps = self.step;
+            cli.max_duration = REPLAY_DURATION;
+        }
+        Ok(cli)
+    }
+
+    /// Verifies that a run reproduced the paths recorded in the checkpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths of every drone of the run
+    ///
+    /// # Returns
+    ///
+    /// An error describing the first path that differs, if any
+    pub fn verify(&self, paths: &[Vec<Path>]) -> Result<()> {
+        let recorded = self.fleet.paths();
+        if recorded.len() != paths.len() {
+            anyhow::bail!(
+                "Replay has {} drones, the recording {}",
+                paths.len(),
+                recorded.len()
+            );
+        }
+        for (drone, (expected, actual)) in recorded.iter().zip(paths).enumerate() {
+            let diverged = expected
+                .iter()
+                .zip(actual)
+                .position(|(a, b)| a.steps != b.steps)
+                .or((expected.len() != actual.len()).then(|| expected.len().min(actual.len())));
+            if let Some(index) = diverged {
+                anyhow::bail!(
+                    "Drone {} diverged from the recording at path {}",
+                    drone,
+                    index
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::{Genetic, Orienteering, RayCasting};
+    use crate::run::main::{resume_fleet_simulation, run_fleet_simulation};
+    use crate::simulators::Incremental;
+
+    /// Tests that resuming from a checkpoint gives the same paths as an uninterrupted run.
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let args = [
+            "planner", "-t", "12", "-T", "60000", "-x", "2", "-y", "2", "-x", "15", "-y", "15",
+        ];
+        let cli = Cli::parse_from(args);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let planner = RayCasting::new(5, 8);
+        let simulator = Incremental {
+            start_grid: grid.clone(),
+            increment_step: 1,
+        };
+
+        let mut fleet = Fleet::new(&[(2, 2), (15, 15)]);
+        run_fleet_simulation(&cli, &planner, &simulator, &mut fleet, &[], &grid)
+            .expect("Simulation failed");
+        let mut recording = Checkpoint::start(
+            args.map(String::from).to_vec(),
+            &cli,
+            &grid,
+            Fleet::new(&[(2, 2), (15, 15)]),
+        );
+        recording.fleet = fleet;
+        recording.step = 12;
+
+        // Stop half way, then resume from a saved and loaded checkpoint
+        let half = Cli::parse_from(args.map(|arg| if arg == "12" { "6" } else { arg }));
+        let mut checkpoint =
+            Checkpoint::start(Vec::new(), &half, &grid, Fleet::new(&[(2, 2), (15, 15)]));
+        resume_fleet_simulation(&half, &planner, &simulator, &[], &mut checkpoint, &mut [])
+            .expect("Simulation failed");
+        let text = serde_json::to_string(&checkpoint).expect("Could not serialise");
+        let mut resumed: Checkpoint = serde_json::from_str(&text).expect("Could not parse");
+        assert_eq!(resumed.step, 6);
+        resume_fleet_simulation(&cli, &planner, &simulator, &[], &mut resumed, &mut [])
+            .expect("Simulation failed");
+
+        recording
+            .verify(resumed.fleet.paths())
+            .expect("Resumed run should reproduce the recording");
+        assert!(recording.verify(&[Vec::new(), Vec::new()]).is_err());
+        assert_eq!(
+            recording.cli(&cli).expect("Arguments recorded").time_steps,
+            12
+        );
+        assert!(checkpoint.cli(&cli).is_err(), "No arguments were recorded");
+    }
+
+    /// Tests that runs of planners keeping state between steps are neither saved nor resumed.
+    #[test]
+    fn test_stateful_planner_refuses_checkpoints() {
+        let cli = Cli::parse_from(["planner", "-t", "4", "-x", "2", "-y", "2"]);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let planner = Orienteering::new(4, None, 10, 0);
+        let simulator = Incremental {
+            start_grid: grid.clone(),
+            increment_step: 1,
+        };
+
+        let mut checkpoint = Checkpoint::start(Vec::new(), &cli, &grid, Fleet::new(&[(2, 2)]));
+        checkpoint.step = 2;
+        assert!(
+            resume_fleet_simulation(&cli, &planner, &simulator, &[], &mut checkpoint, &mut [])
+                .is_err()
+        );
+
+        let saving = Cli::parse_from(["planner", "-t", "4", "--checkpoint", "unused"]);
+        let mut checkpoint = Checkpoint::start(Vec::new(), &saving, &grid, Fleet::new(&[(2, 2)]));
+        assert!(resume_fleet_simulation(
+            &saving,
+            &planner,
+            &simulator,
+            &[],
+            &mut checkpoint,
+            &mut []
+        )
+        .is_err());
+    }
+
+    /// Tests that planners bounded by time refuse to save checkpoints and to replay them.
+    #[test]
+    fn test_time_bounded_planner_refuses_checkpoints() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let simulator = Incremental {
+            start_grid: grid.clone(),
+            increment_step: 1,
+        };
+        let planner = Genetic {
+            simulator: Incremental {
+                start_grid: grid.clone(),
+                increment_step: 1,
+            },
+            horizon: 4,
+            population: 8,
+            generations: 4,
+            mutation_rate: 0.1,
+            budget: 10,
+            seed: 0,
+        };
+
+        for args in [
+            ["planner", "-t", "4", "--checkpoint", "unused"],
+            ["planner", "-t", "4", "--replay", "unused"],
+        ] {
+            let cli = Cli::parse_from(args);
+            let mut checkpoint = Checkpoint::start(Vec::new(), &cli, &grid, Fleet::new(&[(2, 2)]));
+            assert!(resume_fleet_simulation(
+                &cli,
+                &planner,
+                &simulator,
+                &[],
+                &mut checkpoint,
+                &mut []
+            )
+            .is_err());
+        }
+    }
+}
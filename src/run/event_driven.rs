@@ -0,0 +1,345 @@
+use crate::parsers::Cli;
+use crate::run::fleet::{FleetResults, Lifetime};
+use crate::run::main::followed;
+use crate::traits::{Planner, Simulator};
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::{Budget, Deadline, Overruns};
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinSet;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Timing configuration of the event driven runtime.
+#[derive(Clone, Debug)]
+pub struct EventDrivenConfig {
+    /// Period of the world clock, one world step is applied per period.
+    pub world_period: Duration,
+    /// Planning period of each drone. Drones without an entry plan at the world period.
+    pub drone_periods: Vec<Duration>,
+    /// Transmission delay of a command from a drone to the world.
+    pub latency: Duration,
+}
+
+impl EventDrivenConfig {
+    /// Creates the configuration from the command line arguments.
+    pub fn from_cli(cli: &Cli) -> Self {
+        EventDrivenConfig {
+            world_period: Duration::from_millis(cli.world_period),
+            drone_periods: cli
+                .drone_period
+                .iter()
+                .map(|&ms| Duration::from_millis(ms))
+                .collect(),
+            latency: Duration::from_millis(cli.latency),
+        }
+    }
+
+    /// Returns the planning period of the given drone.
+    pub fn drone_period(&self, drone: usize) -> Duration {
+        self.drone_periods
+            .get(drone)
+            .copied()
+            .unwrap_or(self.world_period)
+    }
+}
+
+/// The state of the world as broadcast to the drones after every world step.
+#[derive(Clone)]
+pub struct WorldSnapshot {
+    /// The world step at which the snapshot was taken.
+    pub step: usize,
+    /// The grid at that step.
+    pub grid: Arc<Grid>,
+    /// The states of all drones at that step.
+    pub drones: Vec<DroneState>,
+}
+
+/// A planned move sent from a drone to the world.
+struct MoveCommand {
+    /// The drone that sent the command.
+    drone: usize,
+    /// The world step of the snapshot the plan was made on.
+    planned_at: usize,
+    /// The planned path, starting at the position of the drone.
+    path: Path,
+}
+
+/// Runs a drone simulation in which every drone is an independent tokio task.
+///
+/// The world owns the grid and advances on its own clock. On every world step it applies the
+/// commands received since the previous step, in arrival order, lets the grid recover once and
+/// broadcasts a new snapshot.
+/// Drones plan on the latest snapshot at their own rate and send their plan back over a channel.
+/// Commands that no longer start at the current position of the drone are rejected as stale.
+///
+/// # Arguments
+///
+/// * `cli` - Command line interface arguments
+/// * `config` - Timing configuration of the world and the drones
+/// * `planner` - An implementation of the Planner trait, shared by all drones
+/// * `simulator` - An implementation of the Simulator trait, owned by the world
+/// * `starting_drones` - Initial states of the drones
+/// * `starting_grid` - The initial state of the grid
+///
+/// # Returns
+///
+/// A Result containing the applied paths of every drone with the value harvested following each
+/// of them, and the number of world steps run, or an error
+pub async fn run_event_driven_simulation(
+    cli: Cli,
+    config: EventDrivenConfig,
+    planner: impl Planner + Send + Sync + 'static,
+    simulator: impl Simulator,
+    starting_drones: Vec<DroneState>,
+    starting_grid: &Grid,
+) -> Result<(FleetResults, usize)> {
+    let periods = (0..starting_drones.len()).map(|drone| config.drone_period(drone));
+    if std::iter::once(config.world_period)
+        .chain(periods)
+        .any(|period| period.is_zero())
+    {
+        anyhow::bail!("The world and drone periods must be longer than zero");
+    }
+    if !cli.fleet_schedule().is_empty() {
+        log::warn!("Fleet schedules are ignored by the event-driven runtime");
+    }
+    if cli.sensor_radius.is_some() {
+        log::warn!("Sensing is not simulated by the event-driven runtime");
+    }
+
+    let planner = Arc::new(planner);
+    let mut drones = starting_drones;
+    let mut paths: Vec<Vec<Path>> = vec![Vec::new(); drones.len()];
+    let mut rewards: Vec<Vec<usize>> = vec![Vec::new(); drones.len()];
+    let mut positions: Vec<Vec<(usize, usize)>> =
+        drones.iter().map(|drone| vec![drone.position]).collect();
+    let mut harvests: Vec<Vec<usize>> = vec![Vec::new(); drones.len()];
+    let mut grid = starting_grid.clone();
+
+    let (snapshot_tx, snapshot_rx) = watch::channel(WorldSnapshot {
+        step: 0,
+        grid: Arc::new(grid.clone()),
+        drones: drones.clone(),
+    });
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel::<MoveCommand>();
+
+    // Spawn drones
+    let mut tasks = JoinSet::new();
+    for drone in 0..drones.len() {
+        tasks.spawn(run_drone(
+            drone,
+            config.drone_period(drone),
+            config.latency,
+            Arc::clone(&planner),
+            snapshot_rx.clone(),
+            command_tx.clone(),
+        ));
+    }
+    drop(command_tx);
+
+    // Run world
+    let mut clock = time::interval(config.world_period);
+    clock.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut deadline = Deadline::from_cli(&cli);
+    let mut current_step = 0;
+    let mut rejected = 0;
+
+    loop {
+        clock.tick().await;
+        current_step += 1;
+
+        // Apply the commands received since the last step
+        let mut harvested = vec![0; drones.len()];
+        while let Ok(command) = command_rx.try_recv() {
+            let state = &mut drones[command.drone];
+            if command.path.steps.front() != Some(&state.position) || command.path.steps.len() < 2 {
+                log::debug!(
+                    "Rejected stale command of drone {} planned at step {}",
+                    command.drone,
+                    command.planned_at
+                );
+                rejected += 1;
+                continue;
+            }
+
+            let mut new_state = state.clone();
+            match simulator.harvest(&grid, &mut new_state, &command.path) {
+                Ok(new_grid) => {
+                    grid = new_grid;
+                    let path = followed(command.path, state.position, new_state.position);
+                    *state = new_state;
+                    paths[command.drone].push(path);
+                    rewards[command.drone].push(state.harvested);
+                    harvested[command.drone] += state.harvested;
+                }
+                Err(e) => log::error!("Simulation error: {:?}", e),
+            }
+        }
+        // The grid recovers once per world step, however many commands were applied
+        simulator.regrow(&mut grid);
+        for (drone, state) in drones.iter().enumerate() {
+            positions[drone].push(state.position);
+            harvests[drone].push(harvested[drone]);
+        }
+
+        log::debug!("Step {}, drones: {:?}", current_step, drones);
+        snapshot_tx.send_replace(WorldSnapshot {
+            step: current_step,
+            grid: Arc::new(grid.clone()),
+            drones: drones.clone(),
+        });
+
+        if current_step == cli.time_steps {
+            log::info!("Max time steps reached");
+            break;
+        }
+
+        deadline.tick();
+        if deadline.will_exceed_deadline() {
+            log::info!("Terminating due to deadline");
+            break;
+        }
+    }
+
+    // Closing the snapshot channel stops the drones
+    drop(snapshot_tx);
+    let mut overruns = Overruns::default();
+    while let Some(result) = tasks.join_next().await {
+        overruns.merge(&result?);
+    }
+
+    log::info!("Rejected {} stale commands", rejected);
+    overruns.log();
+    let lifetimes = vec![
+        Lifetime {
+            joined: 0,
+            left: None,
+        };
+        paths.len()
+    ];
+    let results = FleetResults {
+        paths,
+        rewards,
+        lifetimes,
+        positions,
+        harvests,
+    };
+    Ok((results, current_step))
+}
+
+/// Runs a single drone until the world stops broadcasting snapshots.
+///
+/// Each period the drone waits for a snapshot it has not planned on yet, plans on a blocking
+/// thread within its period and sends the resulting path to the world after the configured
+/// latency. Returns the planning calls that overran the period.
+async fn run_drone(
+    drone: usize,
+    period: Duration,
+    latency: Duration,
+    planner: Arc<impl Planner + Send + Sync + 'static>,
+    mut snapshots: watch::Receiver<WorldSnapshot>,
+    commands: mpsc::UnboundedSender<MoveCommand>,
+) -> Overruns {
+    let mut overruns = Overruns::default();
+    let mut ticker = time::interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    snapshots.mark_changed();
+
+    loop {
+        ticker.tick().await;
+        if snapshots.changed().await.is_err() {
+            break;
+        }
+        let snapshot = snapshots.borrow_and_update().clone();
+        let state = snapshot.drones[drone].clone();
+        let position = state.position;
+
+        // Only avoid the other drones, like the lockstep runner does
+        let mut view = (*snapshot.grid).clone();
+        for other in &snapshot.drones {
+            let pos = other.position;
+            view.saturated_subtract_at(pos.0, pos.1, 2, 5);
+        }
+        view.max(position.0, position.1, 1, &snapshot.grid);
+
+        let planner = Arc::clone(&planner);
+        let plan = tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let budget = Budget::new(period);
+            let path = planner.plan_within(&view, &state, &budget);
+            (path, budget, started.elapsed())
+        })
+        .await;
+        let Ok((plan, budget, elapsed)) = plan else {
+            continue;
+        };
+        overruns.record(elapsed, &budget);
+        let Some(path) = plan else {
+            log::debug!("Path planning finished for drone {}", drone);
+            continue;
+        };
+
+        let command = MoveCommand {
+            drone,
+            planned_at: snapshot.step,
+            path,
+        };
+        let commands = commands.clone();
+        tokio::spawn(async move {
+            time::sleep(latency).await;
+            // The world may already have stopped, in which case the command is dropped
+            let _ = commands.send(command);
+        });
+    }
+
+    overruns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::RayCasting;
+    use crate::simulators::Incremental;
+    use clap::Parser;
+
+    /// Tests that every applied path continues where the previous one ended.
+    #[tokio::test]
+    async fn test_event_driven_paths_are_continuous() {
+        let cli = Cli::parse_from(["planner", "-t", "8", "-T", "5000"]);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let config = EventDrivenConfig {
+            world_period: Duration::from_millis(5),
+            drone_periods: vec![Duration::from_millis(5), Duration::from_millis(15)],
+            latency: Duration::from_millis(1),
+        };
+        let simulator = Incremental {
+            start_grid: grid.clone(),
+            increment_step: 1,
+        };
+        let planner = RayCasting::new(5, 8);
+        let starts = [(2, 2), (15, 15)];
+        let drones = starts.iter().map(|&p| DroneState::new(p)).collect();
+
+        let (results, _) =
+            run_event_driven_simulation(cli, config, planner, simulator, drones, &grid)
+                .await
+                .expect("Simulation failed");
+        let paths = results.paths;
+
+        assert_eq!(paths.len(), starts.len());
+        for (start, drone_paths) in starts.iter().zip(paths.iter()) {
+            assert!(
+                drone_paths.len() <= 8,
+                "At most one command is applied per step"
+            );
+            let mut position = *start;
+            for path in drone_paths {
+                assert_eq!(path.steps[0], position, "Paths should be continuous");
+                position = path.steps[1];
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crate::comms::Network;
+use crate::sensing::Sensor;
+use crate::traits::Planner;
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::Rng;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A change in the composition of the fleet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FleetChange {
+    /// A new drone launches at the given position.
+    Join((usize, usize)),
+    /// The drone with the given id leaves the grid.
+    Leave(usize),
+}
+
+/// A fleet change scheduled at a simulation step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FleetEvent {
+    /// The step at the start of which the change is applied.
+    pub step: usize,
+    /// The change to apply.
+    pub change: FleetChange,
+}
+
+/// What every drone of a finished run did, indexed by drone id.
+#[derive(Clone, Default)]
+pub struct FleetResults {
+    /// The planned paths of every drone, one per step it planned.
+    pub paths: Vec<Vec<Path>>,
+    /// The value every drone harvested following each of its paths.
+    pub rewards: Vec<Vec<usize>>,
+    /// When every drone joined and left.
+    pub lifetimes: Vec<Lifetime>,
+    /// The position of every drone when it joined and at the end of every step it was active.
+    pub positions: Vec<Vec<(usize, usize)>>,
+    /// The value every drone harvested in every step it was active.
+    pub harvests: Vec<Vec<usize>>,
+}
+
+/// The steps between which a drone was part of the fleet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lifetime {
+    /// The step at which the drone joined, 0 for the starting drones.
+    pub joined: usize,
+    /// The step at which the drone left, if it did.
+    pub left: Option<usize>,
+}
+
+impl fmt::Display for Lifetime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "joined at step {}", self.joined)?;
+        match self.left {
+            Some(step) => write!(f, ", left at step {}", step),
+            None => write!(f, ", active until the end"),
+        }
+    }
+}
+
+/// The drones taking part in a simulation.
+///
+/// Drones are identified by the order in which they joined. Drones that leave keep their id and
+/// their paths, so results can always be indexed by drone id. Drones joining mid-run share the
+/// profile, speed and footprint of the first starting drone.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fleet {
+    /// Current states, `None` for drones that have left.
+    drones: Vec<Option<DroneState>>,
+    /// The planned paths of every drone, one per step it was active.
+    paths: Vec<Vec<Path>>,
+    /// The value every drone harvested, one per planned path.
+    rewards: Vec<Vec<usize>>,
+    /// When every drone joined and left.
+    lifetimes: Vec<Lifetime>,
+    /// The position of every drone when it joined and at the end of every step it was active.
+    positions: Vec<Vec<(usize, usize)>>,
+    /// The value every drone harvested in every step it was active.
+    harvests: Vec<Vec<usize>>,
+    /// The state new drones join with, apart from their position.
+    template: DroneState,
+}
+
+impl Fleet {
+    /// Creates a fleet of default drones at the given starting positions.
+    pub fn new(starting_positions: &[(usize, usize)]) -> Self {
+        Self::from_drones(starting_positions.iter().map(|&p| DroneState::new(p)))
+    }
+
+    /// Creates a fleet from the starting states of the drones.
+    pub fn from_drones(starting_drones: impl IntoIterator<Item = DroneState>) -> Self {
+        let mut fleet = Fleet {
+            drones: Vec::new(),
+            paths: Vec::new(),
+            rewards: Vec::new(),
+            lifetimes: Vec::new(),
+            positions: Vec::new(),
+            harvests: Vec::new(),
+            template: DroneState::new((0, 0)),
+        };
+        for drone in starting_drones {
+            if fleet.drones.is_empty() {
+                fleet.template = drone.clone();
+            }
+            fleet.push_drone(0, drone);
+        }
+        fleet
+    }
+
+    /// Gives every drone, including the ones already in the fleet, a fully charged battery.
+    pub fn with_battery(mut self, capacity: Option<f32>) -> Self {
+        self.template.charge = capacity;
+        for drone in self.drones.iter_mut().flatten() {
+            drone.charge = capacity;
+        }
+        self
+    }
+
+    /// Gives every drone, including the ones already in the fleet, an empty belief map.
+    pub fn with_sensing(mut self, sensor: Option<&Sensor>, grid_size: usize) -> Self {
+        let belief = sensor.map(|sensor| sensor.belief(grid_size));
+        self.template.belief = belief.clone();
+        for drone in self.drones.iter_mut().flatten() {
+            drone.belief = belief.clone();
+        }
+        self
+    }
+
+    /// Adds a drone at the given position and returns its id.
+    pub fn add_drone(&mut self, step: usize, position: (usize, usize)) -> usize {
+        let drone = DroneState {
+            position,
+            ..self.template.clone()
+        };
+        self.push_drone(step, drone)
+    }
+
+    /// Adds a drone in the given state and returns its id.
+    fn push_drone(&mut self, step: usize, drone: DroneState) -> usize {
+        let id = self.drones.len();
+        self.positions.push(vec![drone.position]);
+        self.harvests.push(Vec::new());
+        self.drones.push(Some(DroneState { id, ..drone }));
+        self.paths.push(Vec::new());
+        self.rewards.push(Vec::new());
+        self.lifetimes.push(Lifetime {
+            joined: step,
+            left: None,
+        });
+        id
+    }
+
+    /// Removes an active drone from the fleet.
+    pub fn remove_drone(&mut self, step: usize, drone: usize) -> Result<()> {
+        match self.drones.get_mut(drone) {
+            Some(state @ Some(_)) => {
+                *state = None;
+                self.lifetimes[drone].left = Some(step);
+                Ok(())
+            }
+            Some(None) => Err(anyhow::anyhow!("Drone {} already left", drone)),
+            None => Err(anyhow::anyhow!("Unknown drone: {}", drone)),
+        }
+    }
+
+    /// Applies all events scheduled for the given step, in schedule order.
+    ///
+    /// Invalid events are logged and skipped so a bad schedule does not end the run. The planner
+    /// releases what it kept for the drones that left, see `Planner::release`.
+    pub fn apply(&mut self, step: usize, schedule: &[FleetEvent], planner: &impl Planner) {
+        for event in schedule.iter().filter(|event| event.step == step) {
+            match event.change {
+                FleetChange::Join(position) => {
+                    let drone = self.add_drone(step, position);
+                    log::debug!("Drone {} joined at {:?}", drone, position);
+                }
+                FleetChange::Leave(drone) => match self.remove_drone(step, drone) {
+                    Ok(()) => {
+                        planner.release(drone);
+                        log::debug!("Drone {} left", drone);
+                    }
+                    Err(e) => log::warn!("Skipping fleet event at step {}: {}", step, e),
+                },
+            }
+        }
+    }
+
+    /// Returns the ids and states of the active drones, ordered by id.
+    pub fn active(&self) -> Vec<(usize, DroneState)> {
+        self.drones
+            .iter()
+            .enumerate()
+            .filter_map(|(drone, state)| state.clone().map(|s| (drone, s)))
+            .collect()
+    }
+
+    /// Updates the state of an active drone.
+    pub fn update_drone(&mut self, drone: usize, state: DroneState) {
+        if let Some(current) = self.drones[drone].as_mut() {
+            *current = state;
+        }
+    }
+
+    /// Lets every active drone with a belief map observe the true grid around it.
+    pub fn sense(&mut self, sensor: &Sensor, truth: &Grid, step: usize, rng: &mut Rng) {
+        for drone in self.drones.iter_mut().flatten() {
+            if let Some(belief) = drone.belief.as_mut() {
+                sensor.observe(belief, truth, drone.position, step, rng);
+            }
+        }
+    }
+
+    /// Lets the active drones exchange positions, plans and belief maps over the network.
+    pub fn communicate(&mut self, network: &Network, rng: &mut Rng) {
+        let mut ids = Vec::new();
+        let mut plans = Vec::new();
+        let mut drones = Vec::new();
+        for (drone, state) in self.drones.iter_mut().enumerate() {
+            if let Some(state) = state.as_mut() {
+                ids.push(drone);
+                plans.push(
+                    self.paths[drone]
+                        .last()
+                        .map(|path| path.steps.iter().copied().collect())
+                        .unwrap_or_default(),
+                );
+                drones.push(state);
+            }
+        }
+        network.exchange(&ids, &mut drones, &plans, rng);
+    }
+
+    /// Records the path a drone planned in the current step, with nothing harvested yet.
+    pub fn push_path(&mut self, drone: usize, path: Path) {
+        self.paths[drone].push(path);
+        self.rewards[drone].push(0);
+    }
+
+    /// Records the value a drone harvested following its last path, see `DroneState::harvested`.
+    pub fn record_harvest(&mut self, drone: usize, harvested: usize) {
+        if let Some(reward) = self.rewards[drone].last_mut() {
+            *reward = harvested;
+        }
+    }
+
+    /// Records where every active drone ended a step and what it harvested in it.
+    ///
+    /// Drones that did not move in the step harvested nothing. The harvest of every drone is
+    /// reset for the next step, see `DroneState::harvested`.
+    pub fn end_step(&mut self) {
+        for (drone, state) in self.drones.iter_mut().enumerate() {
+            if let Some(state) = state.as_mut() {
+                self.positions[drone].push(state.position);
+                self.harvests[drone].push(std::mem::take(&mut state.harvested));
+            }
+        }
+    }
+
+    /// Returns the planned paths of every drone, indexed by drone id.
+    pub fn paths(&self) -> &[Vec<Path>] {
+        &self.paths
+    }
+
+    /// Returns the value every drone harvested following each of its paths, indexed by drone id.
+    pub fn rewards(&self) -> &[Vec<usize>] {
+        &self.rewards
+    }
+
+    /// Returns the lifetimes of every drone, indexed by drone id.
+    pub fn lifetimes(&self) -> &[Lifetime] {
+        &self.lifetimes
+    }
+
+    /// Logs when drones that did not take part in the whole run joined and left.
+    pub fn log_lifetimes(&self) {
+        for (drone, lifetime) in self.lifetimes.iter().enumerate() {
+            if lifetime.joined != 0 || lifetime.left.is_some() {
+                log::info!("Drone {} {}", drone, lifetime);
+            }
+        }
+    }
+
+    /// Consumes the fleet and returns the planned paths of every drone.
+    pub fn into_paths(self) -> Vec<Vec<Path>> {
+        self.paths
+    }
+
+    /// Consumes the fleet and returns the planned paths, rewards, lifetimes and recorded steps of
+    /// every drone.
+    pub fn into_results(self) -> FleetResults {
+        FleetResults {
+            paths: self.paths,
+            rewards: self.rewards,
+            lifetimes: self.lifetimes,
+            positions: self.positions,
+            harvests: self.harvests,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::Orienteering;
+
+    /// Tests that drones keep their ids and lifetimes when the fleet changes, and that the planner
+    /// forgets the drones that left.
+    #[test]
+    fn test_fleet_join_and_leave() {
+        let mut fleet = Fleet::new(&[(0, 0), (5, 5)]);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let planner = Orienteering::new(6, None, 5, 0);
+        for (_, drone) in fleet.active() {
+            planner.assign(&grid, &drone);
+        }
+        let schedule = vec![
+            FleetEvent {
+                step: 3,
+                change: FleetChange::Join((9, 9)),
+            },
+            FleetEvent {
+                step: 3,
+                change: FleetChange::Leave(0),
+            },
+            FleetEvent {
+                step: 4,
+                change: FleetChange::Leave(0),
+            },
+        ];
+
+        fleet.apply(2, &schedule, &planner);
+        assert_eq!(fleet.active().len(), 2);
+        assert!(planner.tours.tour_of(0).is_some());
+
+        fleet.apply(3, &schedule, &planner);
+        let active: Vec<_> = fleet
+            .active()
+            .into_iter()
+            .map(|(drone, state)| (drone, state.position))
+            .collect();
+        assert_eq!(active, vec![(1, (5, 5)), (2, (9, 9))]);
+        assert_eq!(fleet.lifetimes()[0].left, Some(3));
+        assert_eq!(fleet.lifetimes()[2].joined, 3);
+        assert!(
+            planner.tours.tour_of(0).is_none(),
+            "The tour should be released"
+        );
+        assert!(planner.tours.tour_of(1).is_some());
+
+        // Leaving twice is skipped
+        fleet.apply(4, &schedule, &planner);
+        assert_eq!(fleet.lifetimes()[0].left, Some(3));
+        assert_eq!(fleet.paths().len(), 3);
+
+        // Only active drones record the steps
+        fleet.update_drone(
+            1,
+            DroneState {
+                id: 1,
+                harvested: 7,
+                ..DroneState::new((5, 6))
+            },
+        );
+        fleet.end_step();
+        let results = fleet.into_results();
+        assert_eq!(
+            results.positions,
+            vec![vec![(0, 0)], vec![(5, 5), (5, 6)], vec![(9, 9), (9, 9)]]
+        );
+        assert_eq!(results.harvests, vec![vec![], vec![7], vec![0]]);
+    }
+}
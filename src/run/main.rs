@@ -1,7 +1,11 @@
+use crate::comms::Network;
 use crate::parsers::Cli;
-use crate::traits::{Planner, Simulator};
-use crate::types::{Grid, Path};
-use crate::utils::{plot_paths, Deadline};
+use crate::run::checkpoint::Checkpoint;
+use crate::run::fleet::{Fleet, FleetEvent, FleetResults};
+use crate::sensing::Sensor;
+use crate::traits::{Observer, Planner, Simulator};
+use crate::types::{DroneState, Grid, Move, Path};
+use crate::utils::{create_path_traces, plot_paths, Budget, Deadline, Overruns, Watchdog};
 
 #[allow(unused_imports)]
 use crate::config::grids::*;
@@ -9,10 +13,12 @@ use crate::config::grids::*;
 use anyhow::Result;
 use log::LevelFilter;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Runs a drone simulation based on the provided parameters.
 ///
+/// Drones joining and leaving during the run are taken from the fleet schedule of the `cli`.
+///
 /// # Arguments
 ///
 /// * `cli` - Command line interface arguments
@@ -23,92 +29,575 @@ use std::time::Duration;
 ///
 /// # Returns
 ///
-/// A Result containing a vector of paths for each drone, or an error
+/// A Result containing the paths of every drone, the value harvested following each of them and
+/// when the drone joined and left, or an error
 pub fn run_drone_simulation(
     cli: Cli,
-    planner: impl Planner,
+    planner: impl Planner + Sync,
     simulator: impl Simulator,
     starting_positions: Vec<(usize, usize)>,
     starting_grid: &Grid,
-) -> Result<Vec<Vec<Path>>> {
+) -> Result<FleetResults> {
+    cli.check_positions(&starting_positions, starting_grid.size)?;
+    let schedule = cli.fleet_schedule();
+    let sensor = Sensor::from_cli(&cli);
+    let mut fleet = Fleet::new(&starting_positions)
+        .with_battery(cli.battery)
+        .with_sensing(sensor.as_ref(), starting_grid.size);
+    run_fleet_simulation(
+        &cli,
+        &planner,
+        &simulator,
+        &mut fleet,
+        &schedule,
+        starting_grid,
+    )?;
+
+    fleet.log_lifetimes();
+    Ok(fleet.into_results())
+}
+
+/// Runs a drone simulation on a fleet whose composition changes over time.
+///
+/// At the start of every step the events of `schedule` for that step are applied to the fleet,
+/// after which all active drones plan and move in lockstep. If sensing is enabled, drones with a
+/// belief map observe their surroundings and plan on their belief instead of the true grid.
+/// With a limited range network they then share what they know with the drones in range.
+///
+/// Every planner call gets an equal share of the time left until the deadline, and the calls
+/// that overrun their share are reported at the end of the run. A watchdog aborts the planner
+/// calls in progress once the deadline passes and the step is then abandoned. The time spent
+/// planning and simulating is reported separately.
+///
+/// # Arguments
+///
+/// * `cli` - Command line interface arguments
+/// * `planner` - An implementation of the Planner trait
+/// * `simulator` - An implementation of the Simulator trait
+/// * `fleet` - The drones taking part, updated in place with their paths
+/// * `schedule` - Drones joining and leaving during the run
+/// * `starting_grid` - The initial state of the grid
+///
+/// # Returns
+///
+/// A Result containing the final state of the grid, or an error
+pub fn run_fleet_simulation(
+    cli: &Cli,
+    planner: &(impl Planner + Sync),
+    simulator: &impl Simulator,
+    fleet: &mut Fleet,
+    schedule: &[FleetEvent],
+    starting_grid: &Grid,
+) -> Result<Grid> {
+    let mut checkpoint = Checkpoint::start(Vec::new(), cli, starting_grid, fleet.clone());
+    let result =
+        resume_fleet_simulation(cli, planner, simulator, schedule, &mut checkpoint, &mut []);
+    *fleet = checkpoint.fleet;
+    result.map(|_| checkpoint.grid)
+}
+
+/// Continues a drone simulation from a checkpoint, see [`run_fleet_simulation`].
+///
+/// The checkpoint is updated at the end of every step and saved to the checkpoint directory of
+/// the `cli` when due. Steps abandoned by the watchdog are not saved. Planners that keep state
+/// between steps or plan depending on the time they are given, see `Planner::keeps_state` and
+/// `Planner::depends_on_time`, can neither save checkpoints nor resume or replay from one.
+/// The observers are told about every move and every step.
+///
+/// # Arguments
+///
+/// * `cli` - Command line interface arguments
+/// * `planner` - An implementation of the Planner trait
+/// * `simulator` - An implementation of the Simulator trait
+/// * `schedule` - Drones joining and leaving during the run
+/// * `checkpoint` - The state to continue from, updated in place
+/// * `observers` - The observers of the run
+///
+/// # Returns
+///
+/// A Result that is an error if the planner keeps state the checkpoint cannot record or depends
+/// on time, or if the simulation, saving a checkpoint or an observer failed
+pub fn resume_fleet_simulation(
+    cli: &Cli,
+    planner: &(impl Planner + Sync),
+    simulator: &impl Simulator,
+    schedule: &[FleetEvent],
+    checkpoint: &mut Checkpoint,
+    observers: &mut [Box<dyn Observer>],
+) -> Result<()> {
     // Prepare simulation
-    let mut positions = starting_positions.clone();
-    let mut paths: Vec<Vec<Path>> = vec![Vec::new(); positions.len()];
-    let mut grid: Result<Grid> = Ok(starting_grid.clone());
+    let checkpointed = cli.checkpoint.is_some() || cli.replay.is_some() || checkpoint.step > 0;
+    if planner.keeps_state() && checkpointed {
+        anyhow::bail!("Checkpoints do not record the state the planner keeps between steps");
+    }
+    if planner.depends_on_time() && checkpointed {
+        anyhow::bail!("Runs of planners bounded by time cannot be reproduced from a checkpoint");
+    }
     let max_steps: usize = cli.time_steps;
+    let sensor = Sensor::from_cli(cli);
+    let network = Network::from_cli(cli);
+    if checkpoint.step >= max_steps {
+        log::info!("Max time steps reached");
+        for observer in observers.iter_mut() {
+            observer.finish()?;
+        }
+        return Ok(());
+    }
 
     // Run simulation
-    let mut current_step = 0;
-    let mut deadline = Deadline::new(cli.max_duration as f32);
+    let mut current_step = checkpoint.step;
+    let mut deadline = Deadline::from_cli(cli);
+    let mut overruns = Overruns::default();
+    let watchdog = Watchdog::start(&deadline);
 
     loop {
         current_step += 1;
+        checkpoint.fleet.apply(current_step, schedule, planner);
+        if let Some(sensor) = &sensor {
+            checkpoint
+                .fleet
+                .sense(sensor, &checkpoint.grid, current_step, &mut checkpoint.rng);
+        }
+        if let Some(network) = &network {
+            checkpoint.fleet.communicate(network, &mut checkpoint.rng);
+        }
+        let (drones, states): (Vec<usize>, Vec<DroneState>) =
+            checkpoint.fleet.active().into_iter().unzip();
 
         // Prepare Grid
-        let mut global_grid = match grid.as_mut() {
-            Ok(g) => g.clone(),
-            Err(_) => return Ok(paths),
-        };
+        let reference_grid = checkpoint.grid.clone();
+        let global_grid = penalise_drones(&checkpoint.grid, &states);
+
+        log::debug!("Grid: \n {:?}", global_grid);
+        log::debug!("Drones: {:?}", states);
 
-        let reference_grid = global_grid.clone();
-        for pos in &positions {
-            global_grid.saturated_subtract_at(pos.0, pos.1, 2, 5);
+        // Plan Actions
+        // WARNING: Current algorithm is not fully resistant to drone convergence and
+        // collapse
+        let jobs = planning_jobs(cli.jobs, states.len());
+        let steps_left = max_steps.saturating_sub(current_step) + 1;
+        let budget = Budget::new(planning_budget(
+            deadline.remaining(),
+            steps_left,
+            states.len(),
+            jobs,
+        ))
+        .with_abort(watchdog.flag());
+        let plans = deadline.time("plan", || {
+            plan_in_parallel(
+                planner,
+                &global_grid,
+                &reference_grid,
+                &states,
+                jobs,
+                &budget,
+                &mut overruns,
+            )
+        });
+        if watchdog.fired() {
+            log::warn!(
+                "Deadline passed while planning, abandoning step {}",
+                current_step
+            );
+            return finish(&overruns, &deadline, observers);
         }
 
-        log::debug!("Grid: \n {:?}", global_grid);
-        log::debug!("Positions: {:?}", positions);
-
-        // Define per-drone configuration
-        let mut private_grid = global_grid.clone();
-        let n_pos = positions.len();
-        for (index, private_location) in positions.iter_mut().enumerate() {
-            private_grid.max(private_location.0, private_location.1, 1, &reference_grid);
-
-            // Plan Action
-            // WARNING: Current algorithm is not fully resistant to drone convergence and
-            // collapse
-            match planner.solve(&private_grid, *private_location) {
-                Some(path) => {
-                    paths[index].push(path.clone());
-
-                    // Simulate Result
-                    // BUG: Simulator will increment n_drones times in 1 iteration
-                    match simulator.solve(&private_grid, &path) {
-                        Ok((new_grid, new_location)) => {
-                            if index != n_pos - 1 {
-                                private_grid = new_grid;
-                            } else {
-                                grid = Ok(new_grid);
-                            }
-                            *private_location = new_location;
-                        }
-                        Err(e) => {
-                            log::error!("Simulation error: {:?}", e);
-                            break;
+        // Simulate Results, in drone order
+        let aborted = deadline.time("simulate", || -> Result<bool> {
+            for ((drone, mut state), plan) in drones.into_iter().zip(states).zip(plans) {
+                let Some(path) = plan else {
+                    log::debug!("Path planning finished for drone {}", drone);
+                    continue;
+                };
+                if watchdog.fired() {
+                    return Ok(true);
+                }
+
+                // BUG: Simulator will increment n_drones times in 1 iteration
+                let from = state.position;
+                match simulator.advance(&checkpoint.grid, &mut state, &path) {
+                    Ok(new_grid) => {
+                        let path = followed(path, from, state.position);
+                        let event = Move {
+                            step: current_step,
+                            drone,
+                            from,
+                            to: state.position,
+                            path: &path,
+                            reward: state.harvested,
+                            before: &checkpoint.grid,
+                            after: &new_grid,
+                        };
+                        for observer in observers.iter_mut() {
+                            observer.on_move(&event)?;
                         }
+                        checkpoint.grid = new_grid;
+                        checkpoint.fleet.push_path(drone, path);
+                        checkpoint.fleet.record_harvest(drone, state.harvested);
+                        checkpoint.fleet.update_drone(drone, state);
+                    }
+                    Err(e) => {
+                        log::error!("Simulation error for drone {}: {:?}", drone, e);
+                        continue;
                     }
-                }
-                _ => {
-                    log::debug!("Path planning finished");
-                    break;
                 }
             }
+            Ok(false)
+        })?;
+        if aborted {
+            log::warn!(
+                "Deadline passed while simulating, abandoning step {}",
+                current_step
+            );
+            return finish(&overruns, &deadline, observers);
         }
+        checkpoint.fleet.end_step();
 
         if log::max_level() >= LevelFilter::Debug {
-            plot_paths(&reference_grid, &paths);
-            thread::sleep(Duration::from_millis(100));
+            let fleet = &checkpoint.fleet;
+            let traces = create_path_traces(fleet.paths(), fleet.rewards());
+            plot_paths(&reference_grid, &traces);
+        }
+        for observer in observers.iter_mut() {
+            observer.on_step(current_step, &checkpoint.grid, &checkpoint.fleet)?;
         }
 
-        if current_step == max_steps {
+        let last = if current_step == max_steps {
             log::info!("Max time steps reached");
-            return Ok(paths);
+            true
+        } else {
+            deadline.tick();
+            let exceeded = deadline.will_exceed_deadline();
+            if exceeded {
+                log::info!("Terminating due to deadline");
+            }
+            exceeded
+        };
+
+        checkpoint.step = current_step;
+        checkpoint.save_if_due(cli, last)?;
+        if last {
+            return finish(&overruns, &deadline, observers);
+        }
+    }
+}
+
+/// Returns the path a drone followed in a step, staying put if it held its position instead of
+/// moving along the planned path.
+pub(crate) fn followed(path: Path, from: (usize, usize), to: (usize, usize)) -> Path {
+    if to == from && path.steps.get(1).is_some_and(|&next| next != from) {
+        Path {
+            steps: [from; 2].into(),
+            total_cost: 0,
+        }
+    } else {
+        path
+    }
+}
+
+/// Reports the planner overruns and the time spent per phase at the end of a run, and tells the
+/// observers the run is over.
+fn finish(
+    overruns: &Overruns,
+    deadline: &Deadline,
+    observers: &mut [Box<dyn Observer>],
+) -> Result<()> {
+    overruns.log();
+    deadline.log_phases();
+    for observer in observers.iter_mut() {
+        observer.finish()?;
+    }
+    Ok(())
+}
+
+/// Resolves the number of planning threads to use.
+///
+/// Falls back to the available parallelism of the machine and never spawns more threads than
+/// there are drones.
+pub fn planning_jobs(requested: Option<usize>, n_drones: usize) -> usize {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    requested.unwrap_or(available).min(n_drones).max(1)
+}
+
+/// Divides the time left over the planner calls still to come.
+///
+/// Drones planning on the same thread share the time of a step, drones on different threads
+/// plan at the same time.
+///
+/// # Arguments
+///
+/// * `remaining` - The time left until the deadline
+/// * `steps_left` - The number of steps still to plan, including the current one
+/// * `n_drones` - The number of drones planning every step
+/// * `jobs` - Number of threads to plan on
+pub fn planning_budget(
+    remaining: Duration,
+    steps_left: usize,
+    n_drones: usize,
+    jobs: usize,
+) -> Duration {
+    let calls_per_thread = n_drones.div_ceil(jobs.max(1)).max(1);
+    remaining / (steps_left.max(1) * calls_per_thread) as u32
+}
+
+/// Penalises the cells around every drone, so drones avoid each other.
+pub fn penalise_drones(grid: &Grid, drones: &[DroneState]) -> Grid {
+    let mut penalised = grid.clone();
+    for drone in drones {
+        let pos = drone.position;
+        penalised.saturated_subtract_at(pos.0, pos.1, 2, 5);
+    }
+    penalised
+}
+
+/// Penalises the belief of a drone around itself and the drones it knows of.
+///
+/// Drones in contact with peers only avoid those peers and the cells they plan to visit. Drones
+/// without peer information avoid all drones.
+fn penalise_belief(drone: &DroneState, drones: &[DroneState]) -> Option<Grid> {
+    let belief = drone.belief.as_ref()?;
+    let Some(peers) = &belief.peers else {
+        return Some(penalise_drones(&belief.grid, drones));
+    };
+
+    let mut penalised = penalise_drones(&belief.grid, std::slice::from_ref(drone));
+    for peer in peers {
+        let pos = peer.position;
+        penalised.saturated_subtract_at(pos.0, pos.1, 2, 5);
+        for &(x, y) in peer.plan.iter().skip(1) {
+            penalised.saturated_subtract_at(x, y, 0, 5);
         }
+    }
+    Some(penalised)
+}
+
+/// Plans the next action of every drone concurrently against a shared snapshot of the grid.
+///
+/// Each drone sees the snapshot with the repulsion around its own position undone, so drones
+/// only avoid each other. Drones with a belief map plan on their belief, penalised for the drones
+/// they know of. The planner first assigns every drone in drone id order, see `Planner::assign`.
+/// Drones are then split in contiguous chunks over `jobs` scoped threads, which keeps the
+/// returned plans in drone order regardless of scheduling. Every call gets its own copy of
+/// `budget`, restarted when the call starts, and is timed against it.
+///
+/// # Arguments
+///
+/// * `planner` - The planner shared by all drones
+/// * `snapshot` - The grid with all drone positions penalised
+/// * `reference_grid` - The grid without any penalties
+/// * `drones` - Current states of the drones
+/// * `jobs` - Number of threads to plan on
+/// * `budget` - The budget every planner call gets a fresh copy of
+/// * `overruns` - Records the calls that took longer than their budget
+///
+/// # Returns
+///
+/// One planning result per drone, in the same order as `drones`
+pub fn plan_in_parallel(
+    planner: &(impl Planner + Sync),
+    snapshot: &Grid,
+    reference_grid: &Grid,
+    drones: &[DroneState],
+    jobs: usize,
+    budget: &Budget,
+    overruns: &mut Overruns,
+) -> Vec<Option<Path>> {
+    let private_grids: Vec<Grid> = drones
+        .iter()
+        .map(|drone| {
+            let position = drone.position;
+            let (mut private_grid, reference) =
+                match (penalise_belief(drone, drones), &drone.belief) {
+                    (Some(penalised), Some(belief)) => (penalised, &belief.grid),
+                    _ => (snapshot.clone(), reference_grid),
+                };
+            private_grid.max(position.0, position.1, 1, reference);
+            private_grid
+        })
+        .collect();
+
+    // Settle shared decisions one drone at a time, so they do not depend on thread scheduling
+    let mut order: Vec<usize> = (0..drones.len()).collect();
+    order.sort_by_key(|&index| drones[index].id);
+    for index in order {
+        planner.assign(&private_grids[index], &drones[index]);
+    }
+
+    let plan = |(drone, private_grid): (&DroneState, &Grid)| {
+        let started = Instant::now();
+        let call_budget = budget.restart();
+        let path = planner.plan_within(private_grid, drone, &call_budget);
+        (path, call_budget, started.elapsed())
+    };
+
+    let work: Vec<(&DroneState, &Grid)> = drones.iter().zip(&private_grids).collect();
+    let timed: Vec<_> = if jobs <= 1 || drones.len() <= 1 {
+        work.into_iter().map(plan).collect()
+    } else {
+        let chunk_size = drones.len().div_ceil(jobs);
+        thread::scope(|scope| {
+            let handles: Vec<_> = work
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().copied().map(plan).collect::<Vec<_>>())
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("Planning thread panicked"))
+                .collect()
+        })
+    };
+
+    timed
+        .into_iter()
+        .map(|(path, call_budget, elapsed)| {
+            overruns.record(elapsed, &call_budget);
+            path
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::{Claims, Frontier, Orienteering, RayCasting};
+    use crate::sensing::Sensor;
+    use crate::utils::Rng;
+    use std::sync::Arc;
+
+    /// Tests that parallel planning returns the same plans, in order, as sequential planning.
+    #[test]
+    fn test_plan_in_parallel_is_deterministic() {
+        let planner = RayCasting::new(5, 8);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let drones: Vec<DroneState> = [(1, 1), (10, 10), (18, 3), (5, 15), (12, 7)]
+            .into_iter()
+            .map(DroneState::new)
+            .collect();
+
+        let budget = Budget::new(Duration::from_secs(1));
+        let mut overruns = Overruns::default();
+        let sequential =
+            plan_in_parallel(&planner, &grid, &grid, &drones, 1, &budget, &mut overruns);
+        let parallel = plan_in_parallel(&planner, &grid, &grid, &drones, 3, &budget, &mut overruns);
+
+        assert_eq!(sequential.len(), drones.len());
+        for (a, b) in sequential.iter().zip(parallel.iter()) {
+            let a = a.as_ref().map(|p| p.steps.clone());
+            let b = b.as_ref().map(|p| p.steps.clone());
+            assert_eq!(a, b, "Parallel plans should match sequential plans");
+        }
+        assert_eq!(overruns.calls, 2 * drones.len());
+    }
+
+    /// Tests that drones planning frontiers in parallel claim them as when planning in order.
+    #[test]
+    fn test_plan_in_parallel_assigns_frontiers_in_order() {
+        let truth = Grid::load("GRID_S").expect("Could not load grid");
+        let sensor = Sensor {
+            radius: 1,
+            noise: 0,
+            max_age: None,
+            prior: 10,
+        };
+        let positions = [(5, 5), (5, 6), (6, 5), (6, 6)];
+        let mut rng = Rng::new(0);
+        let drones: Vec<DroneState> = positions
+            .into_iter()
+            .enumerate()
+            .map(|(id, position)| {
+                let mut belief = sensor.belief(truth.size);
+                for &seen in &positions {
+                    sensor.observe(&mut belief, &truth, seen, 0, &mut rng);
+                }
+                DroneState {
+                    id,
+                    belief: Some(belief),
+                    ..DroneState::new(position)
+                }
+            })
+            .collect();
+
+        let budget = Budget::unlimited();
+        let plan = |jobs| {
+            let planner = Frontier {
+                inner: RayCasting::new(5, 8),
+                separation: 2,
+                claims: Arc::new(Claims::default()),
+            };
+            let mut overruns = Overruns::default();
+            let plans: Vec<_> = plan_in_parallel(
+                &planner,
+                &truth,
+                &truth,
+                &drones,
+                jobs,
+                &budget,
+                &mut overruns,
+            )
+            .into_iter()
+            .map(|path| path.map(|path| path.steps))
+            .collect();
+            let claims: Vec<_> = (0..drones.len())
+                .map(|drone| planner.claims.claim_of(drone))
+                .collect();
+            (plans, claims)
+        };
+
+        let (sequential, claims) = plan(1);
+        for _ in 0..5 {
+            let (parallel, parallel_claims) = plan(4);
+            assert_eq!(
+                parallel_claims, claims,
+                "Claims should not depend on threads"
+            );
+            assert_eq!(parallel, sequential);
+        }
+        let claimed: Vec<_> = claims.iter().flatten().collect();
+        for (i, a) in claimed.iter().enumerate() {
+            for b in &claimed[i + 1..] {
+                assert!(a.0.abs_diff(b.0).max(a.1.abs_diff(b.1)) > 2);
+            }
+        }
+    }
+
+    /// Tests that drones planning orienteering tours in parallel get the tours planned in order.
+    #[test]
+    fn test_plan_in_parallel_assigns_tours_in_order() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let drones: Vec<DroneState> = [(2, 2), (3, 3), (12, 12), (13, 13)]
+            .into_iter()
+            .enumerate()
+            .map(|(id, position)| DroneState {
+                id,
+                ..DroneState::new(position)
+            })
+            .collect();
+
+        let budget = Budget::unlimited();
+        let plan = |jobs| {
+            let planner = Orienteering::new(12, None, 20, 0);
+            let mut overruns = Overruns::default();
+            plan_in_parallel(
+                &planner,
+                &grid,
+                &grid,
+                &drones,
+                jobs,
+                &budget,
+                &mut overruns,
+            )
+            .into_iter()
+            .map(|path| path.map(|path| path.steps))
+            .collect::<Vec<_>>()
+        };
 
-        deadline.tick();
-        if deadline.will_exceed_deadline() {
-            log::info!("Terminating due to deadline");
-            return Ok(paths);
+        let sequential = plan(1);
+        assert!(sequential.iter().all(Option::is_some));
+        for _ in 0..5 {
+            assert_eq!(plan(4), sequential, "Tours should not depend on threads");
         }
     }
 }
@@ -1,2 +1,18 @@
+pub mod checkpoint;
+pub mod event_driven;
+pub mod fleet;
 pub mod main;
-pub use main::run_drone_simulation;
+pub mod real_time;
+pub mod setup;
+pub mod sweep;
+pub mod tune;
+pub use checkpoint::Checkpoint;
+pub use event_driven::{run_event_driven_simulation, EventDrivenConfig};
+pub use fleet::{Fleet, FleetChange, FleetEvent, FleetResults, Lifetime};
+pub use main::{
+    plan_in_parallel, resume_fleet_simulation, run_drone_simulation, run_fleet_simulation,
+};
+pub use real_time::{run_real_time_simulation, RealTimeConfig};
+pub use setup::Setup;
+pub use sweep::{run_sweep, simulate, Sweep, SweepPoint, SweepRow};
+pub use tune::{run_tune, Candidate, Objective, Search, SearchSpace, Trial, Tuner};
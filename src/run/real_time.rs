@@ -0,0 +1,377 @@
+use crate::comms::Network;
+use crate::parsers::{Cli, OverrunPolicy};
+use crate::run::fleet::{Fleet, FleetEvent};
+use crate::run::main::{
+    followed, penalise_drones, plan_in_parallel, planning_budget, planning_jobs,
+};
+use crate::sensing::Sensor;
+use crate::traits::{Planner, Simulator};
+use crate::types::{DroneState, Grid, Path};
+use crate::utils::{create_path_traces, plot_paths, Budget, Overruns, Rng};
+
+use anyhow::Result;
+use log::LevelFilter;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Timing configuration of the real-time runtime.
+#[derive(Clone, Debug)]
+pub struct RealTimeConfig {
+    /// The wall-clock duration of a step.
+    pub tick: Duration,
+    /// What happens to a step whose plans are not ready by the end of its tick.
+    pub policy: OverrunPolicy,
+}
+
+impl RealTimeConfig {
+    /// Creates the configuration from the command line arguments.
+    pub fn from_cli(cli: &Cli) -> Self {
+        RealTimeConfig {
+            tick: Duration::from_secs_f32(1. / cli.tick_rate),
+            policy: cli.overrun,
+        }
+    }
+}
+
+/// Runs a drone simulation in lockstep, paced to the wall clock.
+///
+/// Step `n` owns the tick from `(n - 1) * tick` to `n * tick` after the start. The drones plan on
+/// a blocking thread during the tick and their plans are applied once planning finishes. Plans
+/// that are not ready by the end of the tick are handled according to the overrun policy:
+///
+/// * `Skip` - The late plans are dropped and the drones stay put for the late step and every
+///   tick that passed while waiting for them.
+/// * `Hold` - The late plans are dropped and the drones keep following the rest of their last
+///   plan for those steps.
+/// * `CatchUp` - The late plans are applied and the following steps start right away, without
+///   waiting for their tick, until the run is back on schedule.
+///
+/// Planning is aborted at the end of the tick, unless the policy catches up. The run takes
+/// `cli.time_steps` ticks, the max duration of the `cli` does not apply.
+///
+/// # Arguments
+///
+/// * `cli` - Command line interface arguments
+/// * `config` - The tick and the overrun policy
+/// * `planner` - An implementation of the Planner trait, shared by all drones
+/// * `simulator` - An implementation of the Simulator trait
+/// * `fleet` - The drones taking part, updated in place with their paths
+/// * `schedule` - Drones joining and leaving during the run
+/// * `starting_grid` - The initial state of the grid
+///
+/// # Returns
+///
+/// A Result containing the final state of the grid and the number of steps simulated, not
+/// counting the steps lost to overruns, or an error
+pub async fn run_real_time_simulation(
+    cli: &Cli,
+    config: RealTimeConfig,
+    planner: Arc<impl Planner + Send + Sync + 'static>,
+    simulator: &impl Simulator,
+    fleet: &mut Fleet,
+    schedule: &[FleetEvent],
+    starting_grid: &Grid,
+) -> Result<(Grid, usize)> {
+    // Prepare simulation
+    let mut grid = starting_grid.clone();
+    let max_steps: usize = cli.time_steps;
+    let sensor = Sensor::from_cli(cli);
+    let network = Network::from_cli(cli);
+    let mut rng = Rng::new(cli.seed);
+    let tick = config.tick;
+
+    // Run simulation
+    let start = Instant::now();
+    let mut steps_done = 0;
+    let mut overruns = Overruns::default();
+    let mut late_steps = 0;
+    let mut lost_steps = 0;
+    let mut worst = Duration::ZERO;
+    let mut held: HashMap<usize, Path> = HashMap::new();
+
+    while steps_done < max_steps {
+        let current_step = steps_done + 1;
+        time::sleep_until(start + tick * steps_done as u32).await;
+
+        fleet.apply(current_step, schedule, &*planner);
+        if let Some(sensor) = &sensor {
+            fleet.sense(sensor, &grid, current_step, &mut rng);
+        }
+        if let Some(network) = &network {
+            fleet.communicate(network, &mut rng);
+        }
+        let (drones, states): (Vec<usize>, Vec<DroneState>) = fleet.active().into_iter().unzip();
+
+        // Plan Actions, on a blocking thread so the tick can pass while planning
+        let reference_grid = grid.clone();
+        let global_grid = penalise_drones(&grid, &states);
+        let jobs = planning_jobs(cli.jobs, states.len());
+        let abort = Arc::new(AtomicBool::new(false));
+        let budget = Budget::new(planning_budget(tick, 1, states.len(), jobs))
+            .with_abort(Arc::clone(&abort));
+        let mut planning = {
+            let planner = Arc::clone(&planner);
+            let states = states.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut overruns = Overruns::default();
+                let plans = plan_in_parallel(
+                    &*planner,
+                    &global_grid,
+                    &reference_grid,
+                    &states,
+                    jobs,
+                    &budget,
+                    &mut overruns,
+                );
+                (plans, overruns)
+            })
+        };
+
+        let due = start + tick * current_step as u32;
+        // Plans that are ready once the tick has passed are late all the same
+        let on_time = tokio::select! {
+            biased;
+            _ = time::sleep_until(due) => None,
+            planned = &mut planning => Some(planned?),
+        };
+        let plans = match on_time {
+            Some((plans, calls)) => {
+                overruns.merge(&calls);
+                Some(plans)
+            }
+            None => {
+                if config.policy != OverrunPolicy::CatchUp {
+                    abort.store(true, Ordering::Relaxed);
+                }
+                let (plans, calls) = planning.await?;
+                overruns.merge(&calls);
+
+                let late = Instant::now().saturating_duration_since(due);
+                log::debug!("Step {} overran its tick by {:?}", current_step, late);
+                late_steps += 1;
+                worst = worst.max(late);
+                (config.policy == OverrunPolicy::CatchUp).then_some(plans)
+            }
+        };
+
+        match plans {
+            Some(plans) => {
+                // Simulate Results, in drone order
+                for ((drone, state), plan) in drones.into_iter().zip(states).zip(plans) {
+                    let Some(path) = plan else {
+                        log::debug!("Path planning finished for drone {}", drone);
+                        held.remove(&drone);
+                        continue;
+                    };
+                    if let Err(e) =
+                        follow(simulator, &mut grid, fleet, &mut held, drone, state, path)
+                    {
+                        log::error!("Simulation error for drone {}: {:?}", drone, e);
+                        continue;
+                    }
+                }
+                fleet.end_step();
+                steps_done = current_step;
+            }
+            None => {
+                // The late step and the ticks that passed since are lost, resume at the next tick
+                let elapsed = Instant::now().saturating_duration_since(start).as_nanos();
+                let resume = (elapsed.div_ceil(tick.as_nanos().max(1)) as usize)
+                    .clamp(current_step, max_steps);
+                for step in current_step..=resume {
+                    if step > current_step {
+                        fleet.apply(step, schedule, &*planner);
+                    }
+                    if config.policy == OverrunPolicy::Hold {
+                        hold(simulator, &mut grid, fleet, &mut held);
+                    }
+                    fleet.end_step();
+                }
+                lost_steps += resume - current_step + 1;
+                steps_done = resume;
+            }
+        }
+
+        if log::max_level() >= LevelFilter::Debug {
+            plot_paths(&grid, &create_path_traces(fleet.paths(), fleet.rewards()));
+        }
+    }
+
+    log::info!("Max time steps reached");
+    if late_steps == 0 {
+        log::info!("All {} steps kept to their tick of {:?}", max_steps, tick);
+    } else {
+        log::warn!(
+            "{} of {} steps overran their tick of {:?}, worst by {:?}, {} steps lost",
+            late_steps,
+            max_steps,
+            tick,
+            worst,
+            lost_steps
+        );
+    }
+    overruns.log();
+    Ok((grid, steps_done - lost_steps))
+}
+
+/// Moves every active drone along the rest of its last plan, if any is left.
+fn hold(
+    simulator: &impl Simulator,
+    grid: &mut Grid,
+    fleet: &mut Fleet,
+    held: &mut HashMap<usize, Path>,
+) {
+    for (drone, state) in fleet.active() {
+        let Some(path) = held.remove(&drone) else {
+            continue;
+        };
+        if path.steps.front() != Some(&state.position) {
+            continue;
+        }
+        if let Err(e) = follow(simulator, grid, fleet, held, drone, state, path) {
+            log::error!("Simulation error for drone {}: {:?}", drone, e);
+            continue;
+        }
+    }
+}
+
+/// Moves a drone along a path and keeps the rest of the path to hold on to.
+fn follow(
+    simulator: &impl Simulator,
+    grid: &mut Grid,
+    fleet: &mut Fleet,
+    held: &mut HashMap<usize, Path>,
+    drone: usize,
+    mut state: DroneState,
+    path: Path,
+) -> Result<()> {
+    let from = state.position;
+    *grid = simulator.advance(grid, &mut state, &path)?;
+    fleet.push_path(drone, followed(path.clone(), from, state.position));
+    fleet.record_harvest(drone, state.harvested);
+
+    let rest: VecDeque<(usize, usize)> =
+        match path.steps.iter().skip(1).position(|&p| p == state.position) {
+            Some(index) => path.steps.range(index + 1..).copied().collect(),
+            None => VecDeque::new(),
+        };
+    if rest.len() >= 2 {
+        held.insert(
+            drone,
+            Path {
+                steps: rest,
+                total_cost: path.total_cost,
+            },
+        );
+    } else {
+        held.remove(&drone);
+    }
+    fleet.update_drone(drone, state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planners::RayCasting;
+    use crate::simulators::Incremental;
+    use clap::Parser;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc;
+
+    /// A planner that is too slow for the tick on every other call.
+    ///
+    /// A late call reports itself and waits until the test has advanced the paused clock past
+    /// the tick, so the outcome does not depend on real timing.
+    struct Slow {
+        inner: RayCasting,
+        calls: AtomicUsize,
+        late: mpsc::UnboundedSender<()>,
+        release: Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl Planner for Slow {
+        fn solve(&self, grid: &Grid, start: (usize, usize)) -> Option<Path> {
+            if self.calls.fetch_add(1, Ordering::Relaxed) % 2 == 1 {
+                self.late.send(()).expect("Test stopped listening");
+                self.release.lock().unwrap().recv().ok();
+            }
+            self.inner.solve(grid, start)
+        }
+    }
+
+    /// Runs a single drone with a planner that is late on every other step, returning its paths
+    /// and the number of steps simulated.
+    async fn run_with(policy: OverrunPolicy) -> (Vec<Path>, usize) {
+        let cli = Cli::parse_from(["planner", "-t", "6", "-x", "2", "-y", "2"]);
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let config = RealTimeConfig {
+            tick: Duration::from_millis(20),
+            policy,
+        };
+        let (late, mut late_calls) = mpsc::unbounded_channel();
+        let (released, release) = std::sync::mpsc::channel();
+        let planner = Slow {
+            inner: RayCasting::new(5, 8),
+            calls: AtomicUsize::new(0),
+            late,
+            release: Mutex::new(release),
+        };
+        let simulator = Incremental {
+            start_grid: grid.clone(),
+            increment_step: 1,
+        };
+        let mut fleet = Fleet::new(&[(2, 2)]);
+
+        // Every late call takes 50 ms of the paused clock, two and a half ticks
+        let clock = async {
+            while late_calls.recv().await.is_some() {
+                time::advance(Duration::from_millis(50)).await;
+                released.send(()).expect("Planner stopped waiting");
+            }
+        };
+        let run = run_real_time_simulation(
+            &cli,
+            config,
+            Arc::new(planner),
+            &simulator,
+            &mut fleet,
+            &[],
+            &grid,
+        );
+        let (result, ()) = tokio::join!(run, clock);
+        let (_, steps) = result.expect("Simulation failed");
+        (fleet.into_paths().remove(0), steps)
+    }
+
+    /// Tests how each overrun policy handles late plans, and that paths stay continuous.
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_policies() {
+        let (skip, skip_steps) = run_with(OverrunPolicy::Skip).await;
+        let (hold, hold_steps) = run_with(OverrunPolicy::Hold).await;
+        let (catch_up, catch_up_steps) = run_with(OverrunPolicy::CatchUp).await;
+
+        assert!(skip.len() < 6, "Skipped steps should not move the drone");
+        assert!(
+            skip_steps < 6 && hold_steps < 6,
+            "Lost steps should not count as simulated"
+        );
+        assert_eq!(catch_up_steps, 6);
+        assert!(
+            hold.len() > skip.len(),
+            "Held plans should keep the drone moving"
+        );
+        assert_eq!(catch_up.len(), 6, "Catching up should run every step");
+        for paths in [&skip, &hold, &catch_up] {
+            let mut position = (2, 2);
+            for path in paths {
+                assert_eq!(path.steps[0], position, "Paths should be continuous");
+                position = path.steps[1];
+            }
+        }
+    }
+}
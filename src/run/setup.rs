@@ -0,0 +1,78 @@
+use crate::config::Scenario;
+use crate::parsers::Cli;
+use crate::planners;
+use crate::run::fleet::Fleet;
+use crate::sensing::Sensor;
+use crate::simulators;
+use crate::traits::{Planner, Simulator};
+use crate::types::Grid;
+
+use std::sync::Arc;
+
+/// The planner, simulator and fleet of a run, built from its command line.
+pub struct Setup {
+    /// The planner of every drone.
+    pub planner: Box<dyn Planner + Send + Sync>,
+    /// The simulator applying the paths of the drones.
+    pub simulator: Box<dyn Simulator>,
+    /// The drones at the start of the run.
+    pub fleet: Fleet,
+    /// The drone owning every cell, if the drones cover their own territories.
+    pub territories: Option<Vec<Option<usize>>>,
+}
+
+impl Setup {
+    /// Builds the run of the command line and scenario on the starting grid.
+    ///
+    /// The drones cover their own territories with `--coverage`, and otherwise plan with the
+    /// planners of the scenario. With a battery, the drones return to their stations before
+    /// running out of charge and the simulator charges them there.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli` - The command line arguments
+    /// * `scenario` - The drones and their planners
+    /// * `grid` - The starting grid
+    ///
+    /// # Returns
+    ///
+    /// The planner, simulator and fleet of the run
+    pub fn new(cli: &Cli, scenario: Scenario, grid: &Grid) -> Self {
+        let positions = scenario.positions();
+        let mut territories = None;
+        let mut planner: Box<dyn Planner + Send + Sync> = if cli.coverage {
+            let coverage = planners::Coverage::new(grid.size, &cli.obstacle, cli.swath, &positions);
+            territories = Some(coverage.territories());
+            Box::new(coverage)
+        } else {
+            Box::new(scenario.planner(Arc::new(simulators::Incremental {
+                start_grid: grid.clone(),
+                increment_step: cli.increment_step,
+            })))
+        };
+        let mut simulator: Box<dyn Simulator> = Box::new(simulators::Incremental {
+            start_grid: grid.clone(),
+            increment_step: cli.increment_step,
+        });
+        if let Some(model) = cli.energy_model(&positions) {
+            planner = Box::new(planners::ReturnToBase {
+                inner: planner,
+                model: model.clone(),
+            });
+            simulator = Box::new(simulators::Charging {
+                inner: simulator,
+                model,
+            });
+        }
+        let sensor = Sensor::from_cli(cli);
+        let fleet = Fleet::from_drones(scenario.drones)
+            .with_battery(cli.battery)
+            .with_sensing(sensor.as_ref(), grid.size);
+        Setup {
+            planner,
+            simulator,
+            fleet,
+            territories,
+        }
+    }
+}
@@ -0,0 +1,560 @@
+use crate::config::Scenario;
+use crate::parsers::{Cli, Runtime, SweepArgs, TableFormat};
+use crate::run::checkpoint::Checkpoint;
+use crate::run::event_driven::{run_event_driven_simulation, EventDrivenConfig};
+use crate::run::fleet::FleetResults;
+use crate::run::main::resume_fleet_simulation;
+use crate::run::real_time::{run_real_time_simulation, RealTimeConfig};
+use crate::run::setup::Setup;
+use crate::types::Grid;
+use crate::utils::{DroneMetrics, Metrics};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::any::Any;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// The max duration in ms of a sweep run, long enough for the deadline never to end it.
+const SWEEP_DURATION: usize = 24 * 60 * 60 * 1000;
+
+/// The header of the sweep CSV.
+const HEADER: &str = "grid,drones,positions,rays,ray_len,edge_bias,falloff,increment_step,seed,\
+steps,total_reward,coverage,shared_cells,overlap,fairness,min_distance,revisits,oscillations,idle_steps,error";
+
+/// The values of the swept parameters of a single run.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SweepPoint {
+    /// The source grid.
+    pub grid: String,
+    /// The starting positions of the drones.
+    pub positions: Vec<(usize, usize)>,
+    /// The number of rays of the ray casting planner.
+    pub rays: usize,
+    /// The ray length of the ray casting planner.
+    pub ray_len: usize,
+    /// The edge bias of the ray casting planner.
+    pub edge_bias: f32,
+    /// The falloff of the ray casting planner.
+    pub falloff: f32,
+    /// The value every cell regrows per step.
+    pub increment_step: u8,
+    /// The seed.
+    pub seed: u64,
+}
+
+impl SweepPoint {
+    /// Returns the command line of the run, the `base` with the swept parameters replaced.
+    ///
+    /// Runs plan on a single thread unless the `base` asks for more, as runs are already
+    /// spread over the threads of the sweep. Runs have no deadline, so that every run takes
+    /// the same number of steps whatever the load of the machine. Nothing is written besides
+    /// the table.
+    pub fn cli(&self, base: &Cli) -> Cli {
+        let drones = self.positions.len();
+        Cli {
+            command: None,
+            grid: self.grid.clone(),
+            pos_x: self.positions.iter().map(|&(x, _)| x).collect(),
+            pos_y: self.positions.iter().map(|&(_, y)| y).collect(),
+            rays: vec![self.rays; drones],
+            ray_len: vec![self.ray_len; drones],
+            edge_bias: self.edge_bias,
+            falloff: self.falloff,
+            increment_step: self.increment_step,
+            seed: self.seed,
+            jobs: Some(base.jobs.unwrap_or(1)),
+            max_duration: SWEEP_DURATION,
+            scenario: None,
+            svg: None,
+            png: None,
+            gif: None,
+            trajectory: None,
+            tui: false,
+            checkpoint: None,
+            resume: None,
+            replay: None,
+            ..base.clone()
+        }
+    }
+}
+
+/// The result of a single run of a sweep.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SweepRow {
+    /// The values of the swept parameters.
+    #[serde(flatten)]
+    pub point: SweepPoint,
+    /// The number of time steps run.
+    pub steps: usize,
+    /// The metrics of the run, if it did not fail.
+    pub metrics: Option<Metrics>,
+    /// What went wrong, if the run failed.
+    pub error: Option<String>,
+}
+
+/// A parameter sweep, running every combination of the swept values once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    /// The source grids.
+    pub grids: Vec<String>,
+    /// The starting positions of every fleet.
+    pub fleets: Vec<Vec<(usize, usize)>>,
+    /// The numbers of rays.
+    pub rays: Vec<usize>,
+    /// The ray lengths.
+    pub ray_lens: Vec<usize>,
+    /// The edge biases.
+    pub edge_biases: Vec<f32>,
+    /// The falloffs.
+    pub falloffs: Vec<f32>,
+    /// The values every cell regrows per step.
+    pub increment_steps: Vec<u8>,
+    /// The seeds.
+    pub seeds: Vec<u64>,
+}
+
+impl Sweep {
+    /// Creates the sweep of the command line.
+    ///
+    /// Parameters that are not swept keep the value of the main command line. Every number of
+    /// drones is taken from the start of every set of starting positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli` - The main command line arguments
+    /// * `args` - The swept parameters
+    ///
+    /// # Returns
+    ///
+    /// The sweep, or an error if a set of starting positions is too small
+    pub fn from_cli(cli: &Cli, args: &SweepArgs) -> Result<Self> {
+        if cli.scenario.is_some() {
+            anyhow::bail!("Sweeps build their drones from the command line, not a scenario file");
+        }
+        let starts = if args.starts.is_empty() {
+            vec![cli
+                .pos_x
+                .iter()
+                .copied()
+                .zip(cli.pos_y.iter().copied())
+                .collect()]
+        } else {
+            args.starts.clone()
+        };
+        let mut fleets = Vec::new();
+        for positions in &starts {
+            if args.drones.is_empty() {
+                fleets.push(positions.clone());
+            }
+            for &drones in &args.drones {
+                if drones == 0 || drones > positions.len() {
+                    anyhow::bail!(
+                        "Cannot start {} drones from {} starting positions",
+                        drones,
+                        positions.len()
+                    );
+                }
+                fleets.push(positions[..drones].to_vec());
+            }
+        }
+        if fleets.iter().any(Vec::is_empty) {
+            anyhow::bail!("No starting positions, give them with -x and -y or --starts");
+        }
+
+        Ok(Sweep {
+            grids: swept_or(&args.grids, cli.grid.clone()),
+            fleets,
+            rays: swept_or(&args.rays, cli.rays.first().copied().unwrap_or(16)),
+            ray_lens: swept_or(
+                &args.ray_len,
+                cli.ray_len.first().copied().unwrap_or(cli.size),
+            ),
+            edge_biases: swept_or(&args.edge_bias, cli.edge_bias),
+            falloffs: swept_or(&args.falloff, cli.falloff),
+            increment_steps: swept_or(&args.increment_step, cli.increment_step),
+            seeds: swept_or(&args.seed, cli.seed),
+        })
+    }
+
+    /// Returns every combination of the swept values, the seeds varying fastest.
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = vec![SweepPoint {
+            grid: String::new(),
+            positions: Vec::new(),
+            rays: 0,
+            ray_len: 0,
+            edge_bias: 0.,
+            falloff: 0.,
+            increment_step: 0,
+            seed: 0,
+        }];
+        points = expand(points, &self.grids, |point, grid| {
+            point.grid.clone_from(grid)
+        });
+        points = expand(points, &self.fleets, |point, fleet| {
+            point.positions.clone_from(fleet)
+        });
+        points = expand(points, &self.rays, |point, &rays| point.rays = rays);
+        points = expand(points, &self.ray_lens, |point, &len| point.ray_len = len);
+        points = expand(points, &self.edge_biases, |point, &bias| {
+            point.edge_bias = bias
+        });
+        points = expand(points, &self.falloffs, |point, &falloff| {
+            point.falloff = falloff
+        });
+        points = expand(points, &self.increment_steps, |point, &step| {
+            point.increment_step = step
+        });
+        expand(points, &self.seeds, |point, &seed| point.seed = seed)
+    }
+
+    /// Runs every combination of the swept values.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The main command line arguments, giving every parameter that is not swept
+    /// * `parallel` - The number of runs at the same time
+    ///
+    /// # Returns
+    ///
+    /// One row per combination, in the order of `points`
+    pub fn run(&self, base: &Cli, parallel: usize) -> Vec<SweepRow> {
+        run_points(base, &self.points(), parallel)
+    }
+}
+
+/// Runs the sweep of the command line and writes its table.
+///
+/// # Arguments
+///
+/// * `cli` - The main command line arguments
+/// * `args` - The swept parameters
+///
+/// # Returns
+///
+/// A Result that is an error if the sweep is invalid or the table could not be written
+pub fn run_sweep(cli: &Cli, args: &SweepArgs) -> Result<()> {
+    let sweep = Sweep::from_cli(cli, args)?;
+    let parallel = args
+        .parallel
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let rows = sweep.run(cli, parallel);
+    let failed = rows.iter().filter(|row| row.error.is_some()).count();
+    if failed > 0 {
+        log::warn!("{} of {} runs failed", failed, rows.len());
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("Failed to create table: {}", path))?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    };
+    match args.table {
+        TableFormat::Csv => write_csv(&rows, &mut out)?,
+        TableFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &rows)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Returns a copy of every point for every value of a parameter, the values varying fastest.
+fn expand<T>(
+    points: Vec<SweepPoint>,
+    values: &[T],
+    set: impl Fn(&mut SweepPoint, &T),
+) -> Vec<SweepPoint> {
+    points
+        .iter()
+        .flat_map(|point| {
+            values.iter().map(|value| {
+                let mut point = point.clone();
+                set(&mut point, value);
+                point
+            })
+        })
+        .collect()
+}
+
+/// Runs the given combinations on `parallel` threads, every thread taking the next combination
+/// once its run finishes. A run that panics is reported as failed without ending the others.
+pub(crate) fn run_points(base: &Cli, points: &[SweepPoint], parallel: usize) -> Vec<SweepRow> {
+    let (next, finished) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let run = || {
+        let mut rows = Vec::new();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(point) = points.get(index) else {
+                return rows;
+            };
+            let cli = point.cli(base);
+            let result = thread::spawn(move || simulate(&cli))
+                .join()
+                .map_err(|panic| anyhow::anyhow!("Run panicked: {}", panic_message(&*panic)))
+                .and_then(|result| result);
+            let row = match result {
+                Ok((grid, results, steps)) => SweepRow {
+                    point: point.clone(),
+                    steps,
+                    metrics: Some(Metrics::new(&grid, &results)),
+                    error: None,
+                },
+                Err(e) => SweepRow {
+                    point: point.clone(),
+                    steps: 0,
+                    metrics: None,
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+            log::info!("Finished run {} of {}", done, points.len());
+            rows.push((index, row));
+        }
+    };
+
+    let mut rows: Vec<(usize, SweepRow)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..parallel.clamp(1, points.len().max(1)))
+            .map(|_| scope.spawn(run))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Sweep thread panicked"))
+            .collect()
+    });
+    rows.sort_by_key(|&(index, _)| index);
+    rows.into_iter().map(|(_, row)| row).collect()
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Runs a simulation of the drones of the command line in its runtime, as the binary does.
+///
+/// # Returns
+///
+/// The starting grid, what every drone did and the number of time steps run, or an error
+pub fn simulate(cli: &Cli) -> Result<(Grid, FleetResults, usize)> {
+    let scenario = Scenario::from_cli(cli);
+    let grid = Grid::load(&cli.grid)?;
+    cli.check_positions(&scenario.positions(), grid.size)?;
+    let Setup {
+        planner,
+        simulator,
+        mut fleet,
+        ..
+    } = Setup::new(cli, scenario, &grid);
+
+    let schedule = cli.fleet_schedule();
+    let (results, steps) = match cli.runtime {
+        Runtime::Lockstep => {
+            let mut checkpoint = Checkpoint::start(Vec::new(), cli, &grid, fleet);
+            resume_fleet_simulation(
+                cli,
+                &planner,
+                &simulator,
+                &schedule,
+                &mut checkpoint,
+                &mut [],
+            )?;
+            let steps = checkpoint.step;
+            (checkpoint.fleet.into_results(), steps)
+        }
+        Runtime::EventDriven => {
+            let config = EventDrivenConfig::from_cli(cli);
+            let drones = fleet.active().into_iter().map(|(_, drone)| drone).collect();
+            runtime()?.block_on(run_event_driven_simulation(
+                cli.clone(),
+                config,
+                planner,
+                simulator,
+                drones,
+                &grid,
+            ))?
+        }
+        Runtime::RealTime => {
+            let config = RealTimeConfig::from_cli(cli);
+            let (_, steps) = runtime()?.block_on(run_real_time_simulation(
+                cli,
+                config,
+                Arc::new(planner),
+                &simulator,
+                &mut fleet,
+                &schedule,
+                &grid,
+            ))?;
+            (fleet.into_results(), steps)
+        }
+    };
+    Ok((grid, results, steps))
+}
+
+/// Returns a runtime for the asynchronous runtimes of a single run.
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")
+}
+
+/// Returns the swept values, or the value of the main command line if the parameter is not swept.
+pub(crate) fn swept_or<T: Clone>(swept: &[T], default: T) -> Vec<T> {
+    if swept.is_empty() {
+        vec![default]
+    } else {
+        swept.to_vec()
+    }
+}
+
+/// Writes the summary metrics of every run as CSV, one row per run.
+pub fn write_csv(rows: &[SweepRow], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "{}", HEADER)?;
+    for row in rows {
+        let point = &row.point;
+        let positions: Vec<String> = point
+            .positions
+            .iter()
+            .map(|(x, y)| format!("{}:{}", x, y))
+            .collect();
+        write!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},",
+            point.grid,
+            point.positions.len(),
+            positions.join(" "),
+            point.rays,
+            point.ray_len,
+            point.edge_bias,
+            point.falloff,
+            point.increment_step,
+            point.seed,
+            row.steps
+        )?;
+        match &row.metrics {
+            Some(metrics) => {
+                let total = |field: fn(&DroneMetrics) -> usize| {
+                    metrics.drones.iter().map(field).sum::<usize>()
+                };
+                write!(
+                    out,
+                    "{},{:.4},{},{:.4},{:.4},{},{},{},{},",
+                    metrics.total_reward,
+                    metrics.coverage,
+                    metrics.shared_cells,
+                    metrics.overlap,
+                    metrics.fairness,
+                    metrics
+                        .min_distance
+                        .map_or(String::new(), |d| d.to_string()),
+                    total(|drone| drone.revisits),
+                    total(|drone| drone.oscillations),
+                    total(|drone| drone.idle_steps)
+                )?;
+            }
+            None => write!(out, ",,,,,,,,,")?,
+        }
+        match &row.error {
+            Some(error) => writeln!(out, "\"{}\"", error.replace('"', "\"\""))?,
+            None => writeln!(out)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::Command;
+    use clap::Parser;
+
+    /// Tests that a sweep runs every combination in order, the same in parallel as in sequence.
+    #[test]
+    fn test_sweep() {
+        let cli = Cli::parse_from([
+            "planner",
+            "-t",
+            "5",
+            "-T",
+            "60000",
+            "sweep",
+            "--starts",
+            "2:2,15:15",
+            "--drones",
+            "1",
+            "--drones",
+            "2",
+            "--rays",
+            "4",
+            "--rays",
+            "8",
+        ]);
+        let Some(Command::Sweep(args)) = &cli.command else {
+            panic!("Expected a sweep");
+        };
+        let sweep = Sweep::from_cli(&cli, args).expect("Invalid sweep");
+        let points = sweep.points();
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[0].positions, vec![(2, 2)]);
+        assert_eq!((points[1].rays, points[2].rays), (8, 4));
+
+        let rows = sweep.run(&cli, 3);
+        assert_eq!(rows, sweep.run(&cli, 1));
+        assert_eq!(
+            rows.iter().map(|row| &row.point).collect::<Vec<_>>(),
+            points.iter().collect::<Vec<_>>()
+        );
+        let metrics = rows[3].metrics.as_ref().expect("Run failed");
+        assert_eq!((rows[3].steps, metrics.drones.len()), (5, 2));
+
+        let mut csv = Vec::new();
+        write_csv(&rows, &mut csv).expect("Could not write");
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv
+            .lines()
+            .nth(3)
+            .unwrap()
+            .starts_with("GRID_S,2,2:2 15:15,4,5,2,1,"));
+
+        let too_many = Cli::parse_from(["planner", "-x", "1", "-y", "1", "sweep", "--drones", "2"]);
+        let Some(Command::Sweep(args)) = &too_many.command else {
+            panic!("Expected a sweep");
+        };
+        assert!(Sweep::from_cli(&too_many, args).is_err());
+    }
+
+    /// Tests that sweep runs take every step whatever the deadline, in the runtime asked for.
+    #[test]
+    fn test_sweep_runs_ignore_deadline() {
+        let cli = Cli::parse_from(["planner", "-t", "5", "-T", "1", "-x", "2", "-y", "2"]);
+        let point = SweepPoint {
+            grid: "GRID_S".to_string(),
+            positions: vec![(2, 2)],
+            rays: 4,
+            ray_len: 5,
+            edge_bias: 2.,
+            falloff: 1.,
+            increment_step: 1,
+            seed: 0,
+        };
+        let rows = run_points(&cli, std::slice::from_ref(&point), 1);
+        assert_eq!((rows[0].steps, &rows[0].error), (5, &None));
+
+        let event_driven = Cli::parse_from(["planner", "-t", "5", "-T", "1", "-r", "event-driven"]);
+        assert_eq!(point.cli(&event_driven).runtime, Runtime::EventDriven);
+        let rows = run_points(&event_driven, &[point], 1);
+        assert_eq!(rows[0].error, None);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::config::Scenario;
+use crate::parsers::{Cli, TuneArgs};
+use crate::run::sweep::{run_points, swept_or, SweepPoint};
+use crate::utils::{Metrics, Rng};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::f32::consts;
+use std::fmt;
+use std::thread;
+
+/// The spread the evolution strategy starts with, as a fraction of the range of a parameter.
+const START_SPREAD: f32 = 0.3;
+
+/// The smallest spread of the evolution strategy, so it never stops exploring entirely.
+const MIN_SPREAD: f32 = 0.05;
+
+/// The metrics the tuner can maximise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Objective {
+    /// The value harvested by all drones together
+    Reward,
+    /// The fraction of the rewarding cells a drone visited
+    Coverage,
+    /// The fairness index of the rewards of the drones
+    Fairness,
+    /// The fraction of the visited cells that only a single drone visited
+    Overlap,
+}
+
+impl Objective {
+    /// Returns the score of a run, higher is better.
+    pub fn score(self, metrics: &Metrics) -> f64 {
+        match self {
+            Objective::Reward => metrics.total_reward as f64,
+            Objective::Coverage => metrics.coverage,
+            Objective::Fairness => metrics.fairness,
+            Objective::Overlap => 1. - metrics.overlap,
+        }
+    }
+}
+
+/// The ways the tuner picks the candidates to evaluate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Search {
+    /// Sample every candidate uniformly from the search space
+    Random,
+    /// Sample around the best candidates of the previous batch, narrowing in on them
+    Evolution,
+}
+
+/// The hyperparameters of the ray casting planner the tuner searches.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Candidate {
+    /// The number of rays.
+    pub rays: usize,
+    /// The ray length.
+    pub ray_len: usize,
+    /// The edge bias.
+    pub edge_bias: f32,
+    /// The falloff.
+    pub falloff: f32,
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rays of length {}, edge bias {:.2}, falloff {:.3}",
+            self.rays, self.ray_len, self.edge_bias, self.falloff
+        )
+    }
+}
+
+/// A candidate and its mean score over the grids and seeds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trial {
+    /// The evaluated hyperparameters.
+    pub candidate: Candidate,
+    /// The mean score of the runs of the candidate.
+    pub score: f64,
+}
+
+/// The ranges the hyperparameters are searched in, as inclusive minimum and maximum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchSpace {
+    /// The range of the number of rays.
+    pub rays: (f32, f32),
+    /// The range of the ray length.
+    pub ray_len: (f32, f32),
+    /// The range of the edge bias.
+    pub edge_bias: (f32, f32),
+    /// The range of the falloff.
+    pub falloff: (f32, f32),
+}
+
+impl SearchSpace {
+    /// Returns the candidate at a point of the unit hypercube, rounding the integer parameters.
+    pub fn candidate(&self, unit: [f32; 4]) -> Candidate {
+        let at = |(min, max): (f32, f32), u: f32| min + u.clamp(0., 1.) * (max - min);
+        Candidate {
+            rays: at(self.rays, unit[0]).round().max(1.) as usize,
+            ray_len: at(self.ray_len, unit[1]).round().max(1.) as usize,
+            edge_bias: at(self.edge_bias, unit[2]),
+            falloff: at(self.falloff, unit[3]),
+        }
+    }
+}
+
+/// A black-box optimiser of the hyperparameters of the ray casting planner.
+///
+/// Candidates are evaluated in batches, every candidate on every grid and seed with the drones
+/// of the main command line, and scored by the mean of the objective over its runs. The
+/// evolution strategy samples every batch from a normal distribution per parameter, moved to
+/// the mean of the better half of the previous batch and narrowed to its spread.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuner {
+    /// The ranges the hyperparameters are searched in.
+    pub space: SearchSpace,
+    /// The metric to maximise.
+    pub objective: Objective,
+    /// How candidates are picked.
+    pub search: Search,
+    /// The number of candidates to evaluate.
+    pub evaluations: usize,
+    /// The number of candidates evaluated together.
+    pub batch: usize,
+    /// The grids every candidate is run on.
+    pub grids: Vec<String>,
+    /// The seeds every candidate is run with.
+    pub seeds: Vec<u64>,
+    /// The seed of the search itself.
+    pub search_seed: u64,
+    /// The number of runs at the same time.
+    pub parallel: usize,
+}
+
+impl Tuner {
+    /// Creates the tuner of the command line.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli` - The main command line arguments, giving the drones and every other option
+    /// * `args` - The options of the search
+    ///
+    /// # Returns
+    ///
+    /// The tuner, or an error if there are no drones to run
+    pub fn from_cli(cli: &Cli, args: &TuneArgs) -> Result<Self> {
+        if cli.scenario.is_some() {
+            anyhow::bail!("The tuner builds its drones from the command line, not a scenario file");
+        }
+        if cli.pos_x.is_empty() || cli.pos_y.is_empty() {
+            anyhow::bail!("No starting positions, give them with -x and -y");
+        }
+        Ok(Tuner {
+            space: SearchSpace {
+                rays: args.rays,
+                ray_len: args.ray_len,
+                edge_bias: args.edge_bias,
+                falloff: args.falloff,
+            },
+            objective: args.objective,
+            search: args.search,
+            evaluations: args.evaluations,
+            batch: args.batch.max(1),
+            grids: swept_or(&args.grids, cli.grid.clone()),
+            seeds: swept_or(&args.seed, cli.seed),
+            search_seed: args.search_seed,
+            parallel: args
+                .parallel
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())),
+        })
+    }
+
+    /// Runs the search.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The main command line arguments, giving the drones and every other option
+    ///
+    /// # Returns
+    ///
+    /// Every trial in the order it was evaluated, or the error of the first failed run
+    pub fn run(&self, base: &Cli) -> Result<Vec<Trial>> {
+        let mut rng = Rng::new(self.search_seed);
+        let mut mean = [0.5; 4];
+        let mut spread = [START_SPREAD; 4];
+        let mut trials: Vec<Trial> = Vec::new();
+
+        while trials.len() < self.evaluations {
+            let size = self.batch.min(self.evaluations - trials.len());
+            let units: Vec<[f32; 4]> = (0..size)
+                .map(|_| match self.search {
+                    Search::Random => std::array::from_fn(|_| rng.next_f32()),
+                    Search::Evolution => std::array::from_fn(|i| {
+                        (mean[i] + spread[i] * normal(&mut rng)).clamp(0., 1.)
+                    }),
+                })
+                .collect();
+            let candidates: Vec<Candidate> = units
+                .iter()
+                .map(|&unit| self.space.candidate(unit))
+                .collect();
+            let scores = self.evaluate(base, &candidates)?;
+
+            if self.search == Search::Evolution {
+                let mut ranked: Vec<usize> = (0..size).collect();
+                ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+                let elite = &ranked[..size.div_ceil(2)];
+                for i in 0..4 {
+                    let n = elite.len() as f32;
+                    mean[i] = elite.iter().map(|&c| units[c][i]).sum::<f32>() / n;
+                    let variance = elite
+                        .iter()
+                        .map(|&c| (units[c][i] - mean[i]).powi(2))
+                        .sum::<f32>()
+                        / n;
+                    spread[i] = variance.sqrt().max(MIN_SPREAD);
+                }
+            }
+
+            trials.extend(
+                candidates
+                    .into_iter()
+                    .zip(scores)
+                    .map(|(candidate, score)| Trial { candidate, score }),
+            );
+            if let Some(best) = best(&trials) {
+                log::info!(
+                    "Evaluated {} of {} candidates, best scored {:.3} with {}",
+                    trials.len(),
+                    self.evaluations,
+                    best.score,
+                    best.candidate
+                );
+            }
+        }
+        Ok(trials)
+    }
+
+    /// Returns the run of a candidate on a grid with a seed.
+    pub fn point(&self, base: &Cli, candidate: &Candidate, grid: &str, seed: u64) -> SweepPoint {
+        SweepPoint {
+            grid: grid.to_string(),
+            positions: base
+                .pos_x
+                .iter()
+                .copied()
+                .zip(base.pos_y.iter().copied())
+                .collect(),
+            rays: candidate.rays,
+            ray_len: candidate.ray_len,
+            edge_bias: candidate.edge_bias,
+            falloff: candidate.falloff,
+            increment_step: base.increment_step,
+            seed,
+        }
+    }
+
+    /// Returns the mean score of every candidate over the grids and seeds.
+    fn evaluate(&self, base: &Cli, candidates: &[Candidate]) -> Result<Vec<f64>> {
+        let points: Vec<SweepPoint> = candidates
+            .iter()
+            .flat_map(|candidate| {
+                self.grids.iter().flat_map(move |grid| {
+                    self.seeds
+                        .iter()
+                        .map(move |&seed| self.point(base, candidate, grid, seed))
+                })
+            })
+            .collect();
+        let runs = self.grids.len() * self.seeds.len();
+        run_points(base, &points, self.parallel)
+            .chunks(runs.max(1))
+            .map(|rows| {
+                let total = rows
+                    .iter()
+                    .map(|row| match &row.metrics {
+                        Some(metrics) => Ok(self.objective.score(metrics)),
+                        None => Err(anyhow::anyhow!(
+                            "Run on {} failed: {}",
+                            row.point.grid,
+                            row.error.as_deref().unwrap_or("no metrics")
+                        )),
+                    })
+                    .sum::<Result<f64>>()?;
+                Ok(total / runs as f64)
+            })
+            .collect()
+    }
+}
+
+/// Tunes the planner of the command line and writes the best configuration as a scenario file.
+///
+/// # Arguments
+///
+/// * `cli` - The main command line arguments
+/// * `args` - The options of the search
+///
+/// # Returns
+///
+/// A Result that is an error if a run failed or the scenario could not be written
+pub fn run_tune(cli: &Cli, args: &TuneArgs) -> Result<()> {
+    let tuner = Tuner::from_cli(cli, args)?;
+    let trials = tuner.run(cli)?;
+    let Some(best) = best(&trials) else {
+        anyhow::bail!("No candidates evaluated");
+    };
+    log::info!(
+        "Best {:?} score {:.3} with {}",
+        tuner.objective,
+        best.score,
+        best.candidate
+    );
+
+    let point = tuner.point(cli, &best.candidate, &cli.grid, cli.seed);
+    let scenario = Scenario::from_cli(&point.cli(cli));
+    match &args.output {
+        Some(path) => scenario.save(path),
+        None => {
+            println!("{}", scenario.to_json()?);
+            Ok(())
+        }
+    }
+}
+
+/// Returns the trial with the highest score, the first one of equal scores.
+pub fn best(trials: &[Trial]) -> Option<&Trial> {
+    trials.iter().reduce(|best, trial| {
+        if trial.score > best.score {
+            trial
+        } else {
+            best
+        }
+    })
+}
+
+/// Returns a sample of the standard normal distribution (Box-Muller).
+fn normal(rng: &mut Rng) -> f32 {
+    let radius = (-2. * (1. - rng.next_f32()).ln()).sqrt();
+    radius * (2. * consts::PI * rng.next_f32()).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PlannerConfig;
+    use crate::parsers::Command;
+    use clap::Parser;
+
+    /// Tests that the tuner stays within its search space, is reproducible and writes a
+    /// scenario with the best candidate.
+    #[test]
+    fn test_tune() {
+        let cli = Cli::parse_from([
+            "planner",
+            "-t",
+            "5",
+            "-T",
+            "60000",
+            "-x",
+            "2",
+            "-y",
+            "2",
+            "-x",
+            "15",
+            "-y",
+            "15",
+            "tune",
+            "--evaluations",
+            "6",
+            "--batch",
+            "3",
+            "--rays",
+            "4:8",
+            "--ray-len",
+            "2:6",
+            "--seed",
+            "1",
+            "--seed",
+            "2",
+        ]);
+        let Some(Command::Tune(args)) = &cli.command else {
+            panic!("Expected a tune");
+        };
+        let tuner = Tuner::from_cli(&cli, args).expect("Invalid tuner");
+        let trials = tuner.run(&cli).expect("Tuning failed");
+        assert_eq!(trials.len(), 6);
+        assert_eq!(trials, tuner.run(&cli).expect("Tuning failed"));
+        for trial in &trials {
+            assert!((4..=8).contains(&trial.candidate.rays));
+            assert!((2..=6).contains(&trial.candidate.ray_len));
+            assert!((0.5..=1.).contains(&trial.candidate.falloff));
+        }
+
+        let best = best(&trials).expect("No trials");
+        assert!(trials.iter().all(|trial| trial.score <= best.score));
+        let point = tuner.point(&cli, &best.candidate, "GRID_S", 0);
+        let scenario = Scenario::from_cli(&point.cli(&cli));
+        let loaded = Scenario::parse(&scenario.to_json().unwrap()).expect("Invalid scenario");
+        assert_eq!(loaded.positions(), vec![(2, 2), (15, 15)]);
+        assert_eq!(
+            loaded.profiles[1].planner,
+            PlannerConfig::RayCasting {
+                len: best.candidate.ray_len,
+                rays: best.candidate.rays,
+                edge_bias: best.candidate.edge_bias,
+                falloff: best.candidate.falloff,
+            }
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::types::Grid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Another drone a drone is in contact with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Peer {
+    /// The id of the other drone.
+    pub drone: usize,
+    /// The position of the other drone.
+    pub position: (usize, usize),
+    /// The cells the other drone last planned to visit, starting at its position.
+    pub plan: Vec<(usize, usize)>,
+}
+
+/// The knowledge a drone has of the grid.
+///
+/// Cells that were never observed, or were forgotten, hold a prior value.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct BeliefMap {
+    /// The believed values of the grid.
+    pub grid: Grid,
+    /// The drones currently in contact, `None` if the positions of all drones are known.
+    pub peers: Option<Vec<Peer>>,
+    /// The step at which each cell was last observed, `None` if it is unknown.
+    seen_at: Vec<Option<u32>>,
+    /// The value assumed for unknown cells.
+    prior: u8,
+}
+
+impl BeliefMap {
+    /// Creates a belief map of a square grid where every cell is unknown.
+    pub fn new(size: usize, prior: u8) -> Self {
+        BeliefMap {
+            grid: Grid::new(vec![prior; size * size]),
+            peers: None,
+            seen_at: vec![None; size * size],
+            prior,
+        }
+    }
+
+    /// Records an observed value of a cell.
+    pub fn record(&mut self, row: usize, col: usize, value: u8, step: usize) {
+        let index = row * self.grid.size + col;
+        self.grid.data[index] = value;
+        self.seen_at[index] = Some(step as u32);
+    }
+
+    /// Returns the cells this map knows more recent values of than `other`.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of (cell index, value, step observed) tuples.
+    pub fn newer_than<'a>(
+        &'a self,
+        other: &'a BeliefMap,
+    ) -> impl Iterator<Item = (usize, u8, usize)> + 'a {
+        self.seen_at
+            .iter()
+            .zip(other.seen_at.iter())
+            .enumerate()
+            .filter_map(|(index, (&mine, &theirs))| match (mine, theirs) {
+                (Some(mine), None) => Some((index, mine)),
+                (Some(mine), Some(theirs)) if mine > theirs => Some((index, mine)),
+                _ => None,
+            })
+            .map(|(index, seen)| (index, self.grid.data[index], seen as usize))
+    }
+
+    /// Merges a value received from another drone, unless a more recent one is known.
+    pub fn merge(&mut self, index: usize, value: u8, seen_at: usize) {
+        if self.seen_at[index].is_none_or(|seen| (seen as usize) < seen_at) {
+            self.grid.data[index] = value;
+            self.seen_at[index] = Some(seen_at as u32);
+        }
+    }
+
+    /// Returns the value assumed for unknown cells.
+    pub fn prior(&self) -> u8 {
+        self.prior
+    }
+
+    /// Returns whether the cell has been observed and not forgotten since.
+    pub fn is_known(&self, row: usize, col: usize) -> bool {
+        self.seen_at[row * self.grid.size + col].is_some()
+    }
+
+    /// Returns the step at which the cell was last observed, if it is known.
+    pub fn seen_at(&self, row: usize, col: usize) -> Option<usize> {
+        self.seen_at[row * self.grid.size + col].map(|step| step as usize)
+    }
+
+    /// Returns the number of known cells.
+    pub fn known_cells(&self) -> usize {
+        self.seen_at.iter().filter(|seen| seen.is_some()).count()
+    }
+
+    /// Forgets every cell that has not been observed for more than `max_age` steps.
+    pub fn age_out(&mut self, step: usize, max_age: usize) {
+        for (value, seen) in self.grid.data.iter_mut().zip(self.seen_at.iter_mut()) {
+            if seen.is_some_and(|seen| step.saturating_sub(seen as usize) > max_age) {
+                *value = self.prior;
+                *seen = None;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for BeliefMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BeliefMap {{ known: {}/{} }}",
+            self.known_cells(),
+            self.seen_at.len()
+        )
+    }
+}
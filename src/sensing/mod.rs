@@ -0,0 +1,4 @@
+pub mod belief;
+pub mod sensor;
+pub use belief::{BeliefMap, Peer};
+pub use sensor::Sensor;
@@ -0,0 +1,103 @@
+use crate::parsers::Cli;
+use crate::sensing::BeliefMap;
+use crate::types::Grid;
+use crate::utils::Rng;
+
+/// A sensor observing the grid in a square around the drone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sensor {
+    /// The radius of the observed square, 0 for only the cell below the drone.
+    pub radius: usize,
+    /// The maximum noise added to or subtracted from every observed value.
+    pub noise: u8,
+    /// The number of steps after which unobserved cells are forgotten, if ever.
+    pub max_age: Option<usize>,
+    /// The value drones assume for cells they do not know.
+    pub prior: u8,
+}
+
+impl Sensor {
+    /// Creates the sensor from the command line arguments, if sensing is enabled.
+    pub fn from_cli(cli: &Cli) -> Option<Self> {
+        cli.sensor_radius.map(|radius| Sensor {
+            radius,
+            noise: cli.sensor_noise,
+            max_age: cli.max_age,
+            prior: cli.belief_prior,
+        })
+    }
+
+    /// Creates an empty belief map for a grid of the given size.
+    pub fn belief(&self, size: usize) -> BeliefMap {
+        BeliefMap::new(size, self.prior)
+    }
+
+    /// Updates a belief map with an observation of the true grid around a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `belief` - The belief map to update.
+    /// * `truth` - The true state of the grid.
+    /// * `position` - The position of the drone.
+    /// * `step` - The current simulation step.
+    /// * `rng` - The random number generator for the noise.
+    pub fn observe(
+        &self,
+        belief: &mut BeliefMap,
+        truth: &Grid,
+        position: (usize, usize),
+        step: usize,
+        rng: &mut Rng,
+    ) {
+        let (row, col) = position;
+        let end_row = (row + self.radius + 1).min(truth.size);
+        let end_col = (col + self.radius + 1).min(truth.size);
+
+        for r in row.saturating_sub(self.radius)..end_row {
+            for c in col.saturating_sub(self.radius)..end_col {
+                let mut value = truth.value_at(r, c);
+                if self.noise > 0 {
+                    let noisy = value as i16 + rng.symmetric(self.noise);
+                    value = noisy.clamp(0, u8::MAX as i16) as u8;
+                }
+                belief.record(r, c, value, step);
+            }
+        }
+
+        if let Some(max_age) = self.max_age {
+            belief.age_out(step, max_age);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that drones only learn the grid around them and forget it again.
+    #[test]
+    fn test_observe_and_age_out() {
+        let truth = Grid::load("GRID_S").expect("Could not load grid");
+        let sensor = Sensor {
+            radius: 1,
+            noise: 0,
+            max_age: Some(2),
+            prior: 7,
+        };
+        let mut rng = Rng::new(0);
+        let mut belief = sensor.belief(truth.size);
+
+        sensor.observe(&mut belief, &truth, (0, 0), 0, &mut rng);
+        assert_eq!(belief.known_cells(), 4);
+        assert_eq!(belief.grid.value_at(1, 1), truth.value_at(1, 1));
+        assert_eq!(belief.grid.value_at(5, 5), 7);
+
+        sensor.observe(&mut belief, &truth, (10, 10), 3, &mut rng);
+        assert!(
+            !belief.is_known(0, 0),
+            "Old observations should be forgotten"
+        );
+        assert_eq!(belief.grid.value_at(0, 0), 7);
+        assert_eq!(belief.known_cells(), 9);
+    }
+}
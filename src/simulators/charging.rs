@@ -0,0 +1,161 @@
+use crate::energy::EnergyModel;
+pub use crate::traits::Simulator;
+pub use crate::types::{DroneState, Grid, Path};
+use anyhow::Result;
+
+/// A simulator that drains the battery of drones as they move and charges them at stations.
+pub struct Charging<S> {
+    /// The simulator that updates the grid and moves the drones.
+    pub inner: S,
+    /// The battery and charging model.
+    pub model: EnergyModel,
+}
+
+impl<S: Simulator> Simulator for Charging<S> {
+    fn solve(&self, grid: &Grid, path: &Path) -> Result<(Grid, (usize, usize))> {
+        self.inner.solve(grid, path)
+    }
+
+    /// Simulates one step of the path and updates the charge of the drone.
+    ///
+    /// A drone without the charge to pay for its move holds its position for the step instead,
+    /// and harvests nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The current state of the grid.
+    /// * `drone` - The state of the drone executing the path.
+    /// * `path` - The path to simulate.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the updated grid, or an error.
+    fn advance(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        self.charged(grid, drone, path, S::advance, true)
+    }
+
+    /// Simulates one step of the path like `advance`, without the grid recovering.
+    fn harvest(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        self.charged(grid, drone, path, S::harvest, false)
+    }
+
+    fn regrow(&self, grid: &mut Grid) {
+        self.inner.regrow(grid)
+    }
+}
+
+impl<S: Simulator> Charging<S> {
+    /// Simulates one step of the path with `step` of the inner simulator, paying for the move
+    /// out of the charge of the drone and charging it at stations. The grid of a stranded drone
+    /// only recovers if `regrow` is set.
+    fn charged(
+        &self,
+        grid: &Grid,
+        drone: &mut DroneState,
+        path: &Path,
+        step: impl Fn(&S, &Grid, &mut DroneState, &Path) -> Result<Grid>,
+        regrow: bool,
+    ) -> Result<Grid> {
+        let Some(charge) = drone.charge else {
+            return step(&self.inner, grid, drone, path);
+        };
+
+        // Pay for every cell passed on the way to the new position, which is the last visit of it
+        // within the reach of the drone
+        let mut moved = drone.clone();
+        let new_grid = step(&self.inner, grid, &mut moved, path)?;
+        let reach = (path.steps.iter().take(drone.speed.max(1) + 1))
+            .rposition(|&step| step == moved.position);
+        let passed = path.steps.iter().take(reach.map_or(1, |reach| reach + 1));
+        let cost = self.model.path_cost(passed);
+
+        // NOTE: Small tolerance for rounding of fractional move costs
+        let (new_grid, charge) = if charge - cost >= -1e-3 {
+            *drone = moved;
+            (new_grid, (charge - cost).max(0.))
+        } else {
+            log::warn!(
+                "Drone {} at {:?} is out of charge, holding position",
+                drone.id,
+                drone.position
+            );
+            let mut held = grid.clone();
+            if regrow {
+                self.inner.regrow(&mut held);
+            }
+            drone.harvested = 0;
+            (held, charge)
+        };
+
+        drone.charge = Some(if self.model.is_station(drone.position) {
+            (charge + self.model.charge_rate).min(self.model.capacity)
+        } else {
+            charge
+        });
+        Ok(new_grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulators::Incremental;
+
+    /// Tests that a drone pays for every move it makes, and holds its position without harvesting
+    /// when it lacks the charge.
+    #[test]
+    fn test_charging_holds_stranded_drone() {
+        let grid = Grid::load("GRID_S").expect("Could not load grid");
+        let simulator = Charging {
+            inner: Incremental {
+                start_grid: grid.clone(),
+                increment_step: 1,
+            },
+            model: EnergyModel {
+                capacity: 10.,
+                straight_cost: 1.,
+                diagonal_cost: 1.5,
+                charge_rate: 2.,
+                stations: vec![(0, 0)],
+            },
+        };
+        let path = Path {
+            steps: [(5, 5), (5, 6)].into(),
+            total_cost: 0,
+        };
+
+        let mut drone = DroneState {
+            charge: Some(1.),
+            ..DroneState::new((5, 5))
+        };
+        simulator
+            .advance(&grid, &mut drone, &path)
+            .expect("Drone should move");
+        assert_eq!((drone.position, drone.charge), ((5, 6), Some(0.)));
+
+        let mut drone = DroneState {
+            charge: Some(0.5),
+            ..DroneState::new((5, 5))
+        };
+        simulator
+            .advance(&grid, &mut drone, &path)
+            .expect("Stranded drone should hold instead of failing");
+        assert_eq!((drone.position, drone.charge), ((5, 5), Some(0.5)));
+        assert_eq!(drone.harvested, 0, "Stranded drone should harvest nothing");
+
+        // A fast drone pays for every move, also when it passes its new position before
+        let mut drone = DroneState {
+            speed: 3,
+            charge: Some(10.),
+            ..DroneState::new((5, 5))
+        };
+        let back_and_forth = Path {
+            steps: [(5, 5), (5, 6), (5, 5), (5, 6)].into(),
+            total_cost: 0,
+        };
+        simulator
+            .advance(&grid, &mut drone, &back_and_forth)
+            .expect("Drone should move");
+        assert_eq!((drone.position, drone.charge), ((5, 6), Some(7.)));
+    }
+}
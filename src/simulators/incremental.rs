@@ -1,5 +1,5 @@
 pub use crate::traits::Simulator;
-pub use crate::types::{Grid, Path};
+pub use crate::types::{DroneState, Grid, Path};
 use anyhow::Result;
 
 /// A simulator that incrementally recovers grid values.
@@ -29,6 +29,52 @@ impl Simulator for Incremental {
 
         Ok((out_grid, path.steps[1]))
     }
+
+    /// Simulates one step of a drone along the path, according to its speed and footprint.
+    ///
+    /// The drone harvests its footprint on every cell it leaves and moves up to `speed` cells
+    /// along the path. The grid recovers once per step. The value taken from the grid, before
+    /// it recovers, is reported as the harvest of the drone.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The current state of the grid.
+    /// * `drone` - The state of the drone executing the path.
+    /// * `path` - The path to simulate.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the updated grid, or an error.
+    fn advance(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        let mut out_grid = self.harvest(grid, drone, path)?;
+        self.recover(&mut out_grid.data);
+        Ok(out_grid)
+    }
+
+    /// Simulates one step of a drone along the path like `advance`, without the grid recovering.
+    fn harvest(&self, grid: &Grid, drone: &mut DroneState, path: &Path) -> Result<Grid> {
+        if path.steps.len() < 2 {
+            return Err(anyhow::anyhow!("Path needs at least 2 steps"));
+        }
+
+        let reach = drone.speed.max(1).min(path.steps.len() - 1);
+        let footprint = drone.footprint;
+        let mut out_grid = grid.clone();
+        let mut harvested = 0;
+        for &(x, y) in path.steps.iter().take(reach) {
+            harvested +=
+                out_grid.saturated_subtract_at(x, y, footprint.radius, footprint.amount as usize);
+        }
+        drone.harvested = harvested;
+
+        drone.position = path.steps[reach];
+        Ok(out_grid)
+    }
+
+    /// Lets every cell regrow by `increment_step`, up to its starting value.
+    fn regrow(&self, grid: &mut Grid) {
+        self.recover(&mut grid.data);
+    }
 }
 
 impl Incremental {
@@ -37,7 +83,7 @@ impl Incremental {
     /// # Arguments
     ///
     /// * `b` - The grid data to recover.
-    fn recover(&self, b: &mut Vec<u8>) {
+    fn recover(&self, b: &mut [u8]) {
         b.iter_mut()
             .zip(self.start_grid.data.iter())
             .for_each(|(b_elem, &a_elem)| {
@@ -80,4 +126,66 @@ mod tests {
             assert_eq!(out_grid.value_at(0, 0), 0);
         }
     }
+
+    /// Tests that fast drones with a wide footprint harvest every cell they pass.
+    #[test]
+    fn test_incremental_advance_speed_and_footprint() {
+        let start_grid = Grid::new(vec![20; 100]);
+        let incremental = Incremental {
+            increment_step: 1,
+            start_grid: start_grid.clone(),
+        };
+        let path = Path {
+            steps: (0..5).map(|i| (i, i)).collect(),
+            total_cost: 0,
+        };
+        let mut drone = DroneState {
+            speed: 3,
+            footprint: crate::types::Footprint {
+                radius: 1,
+                amount: 10,
+            },
+            ..DroneState::new((0, 0))
+        };
+
+        let out_grid = incremental
+            .advance(&start_grid, &mut drone, &path)
+            .expect("Simulation should succeed");
+
+        assert_eq!(drone.position, (3, 3));
+        // (1, 1) is harvested empty from (0, 0), (1, 1) and (2, 2), then recovers once
+        assert_eq!(out_grid.value_at(1, 1), 1);
+        assert_eq!(out_grid.value_at(3, 3), 20 - 10 + 1);
+        assert_eq!(out_grid.value_at(5, 5), 20);
+        // 10 from every cell of the three footprints, except (1, 1) which is empty by (2, 2)
+        assert_eq!(drone.harvested, (4 + 9 + 8) * 10);
+    }
+
+    /// Tests that harvesting and then regrowing a grid is the same as advancing on it.
+    #[test]
+    fn test_incremental_harvest_then_regrow() {
+        let start_grid = Grid::load("GRID_S").unwrap();
+        let incremental = Incremental {
+            increment_step: 3,
+            start_grid: start_grid.clone(),
+        };
+        let mut grid = start_grid.clone();
+        grid.data[0] = 0;
+        let path = Path {
+            steps: [(5, 5), (5, 6)].into(),
+            total_cost: 0,
+        };
+
+        let mut advanced = DroneState::new((5, 5));
+        let expected = incremental.advance(&grid, &mut advanced, &path).unwrap();
+        let mut harvested = DroneState::new((5, 5));
+        let mut out_grid = incremental.harvest(&grid, &mut harvested, &path).unwrap();
+        assert_eq!(out_grid.data[0], 0, "Harvesting should not regrow the grid");
+        incremental.regrow(&mut out_grid);
+        assert_eq!(out_grid.data, expected.data);
+        assert_eq!(
+            (harvested.position, harvested.harvested),
+            (advanced.position, advanced.harvested)
+        );
+    }
 }
@@ -1,2 +1,4 @@
+pub mod charging;
 pub mod incremental;
+pub use charging::Charging;
 pub use incremental::Incremental;
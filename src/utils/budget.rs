@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The time a single planner call may take.
+///
+/// Planners check the budget cooperatively and return their best answer so far once it has
+/// expired. Callers measure whether the budget was overrun. A budget can also be aborted from
+/// outside, for example by a [`Watchdog`](crate::utils::Watchdog).
+#[derive(Clone, Debug)]
+pub struct Budget {
+    /// The moment the call started.
+    start: Instant,
+    /// The time the call may take, `None` if unlimited.
+    limit: Option<Duration>,
+    /// The flag that expires the budget at once when raised.
+    abort: Option<Arc<AtomicBool>>,
+}
+
+impl Budget {
+    /// Creates a budget starting now.
+    pub fn new(limit: Duration) -> Self {
+        Budget {
+            start: Instant::now(),
+            limit: Some(limit),
+            abort: None,
+        }
+    }
+
+    /// Creates a budget that never expires.
+    pub fn unlimited() -> Self {
+        Budget {
+            start: Instant::now(),
+            limit: None,
+            abort: None,
+        }
+    }
+
+    /// Expires the budget as soon as the flag is raised.
+    pub fn with_abort(mut self, abort: Arc<AtomicBool>) -> Self {
+        self.abort = Some(abort);
+        self
+    }
+
+    /// Returns a budget with the same limit and abort flag, starting now.
+    pub fn restart(&self) -> Self {
+        Budget {
+            start: Instant::now(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns whether the budget was aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.abort
+            .as_ref()
+            .is_some_and(|abort| abort.load(Ordering::Relaxed))
+    }
+
+    /// Returns the time the call may take, `None` if unlimited.
+    pub fn limit(&self) -> Option<Duration> {
+        self.limit
+    }
+
+    /// Returns the time left, `None` if unlimited.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.is_aborted() {
+            return Some(Duration::ZERO);
+        }
+        self.limit
+            .map(|limit| limit.saturating_sub(self.start.elapsed()))
+    }
+
+    /// Returns the given time, capped by the time left.
+    pub fn cap(&self, duration: Duration) -> Duration {
+        self.remaining()
+            .map_or(duration, |remaining| remaining.min(duration))
+    }
+
+    /// Checks if the budget has expired.
+    pub fn is_expired(&self) -> bool {
+        self.remaining()
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+}
+
+/// Tracks how often planner calls overran their budget.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overruns {
+    /// The number of measured calls.
+    pub calls: usize,
+    /// The number of calls that took longer than their budget.
+    pub overruns: usize,
+    /// The largest time a call took beyond its budget.
+    pub worst: Duration,
+}
+
+impl Overruns {
+    /// Records the time a call took against its budget.
+    pub fn record(&mut self, elapsed: Duration, budget: &Budget) {
+        self.calls += 1;
+        let Some(limit) = budget.limit() else {
+            return;
+        };
+        if elapsed > limit {
+            self.overruns += 1;
+            self.worst = self.worst.max(elapsed - limit);
+            log::debug!(
+                "Planner overran its budget of {:?} by {:?}",
+                limit,
+                elapsed - limit
+            );
+        }
+    }
+
+    /// Adds the calls recorded by another tracker.
+    pub fn merge(&mut self, other: &Overruns) {
+        self.calls += other.calls;
+        self.overruns += other.overruns;
+        self.worst = self.worst.max(other.worst);
+    }
+
+    /// Logs the number of overruns.
+    pub fn log(&self) {
+        if self.overruns == 0 {
+            log::info!("All {} plans kept to their budget", self.calls);
+        } else {
+            log::warn!(
+                "{} of {} plans overran their budget, worst by {:?}",
+                self.overruns,
+                self.calls,
+                self.worst
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that calls beyond their budget are counted as overruns.
+    #[test]
+    fn test_overruns() {
+        let unlimited = Budget::unlimited();
+        let expired = Budget::new(Duration::ZERO);
+        assert!(!unlimited.is_expired());
+        assert!(expired.is_expired());
+        let abort = Arc::new(AtomicBool::new(false));
+        let abortable = Budget::unlimited().with_abort(Arc::clone(&abort));
+        assert!(!abortable.is_expired());
+        abort.store(true, Ordering::Relaxed);
+        assert!(abortable.restart().is_expired());
+        assert_eq!(
+            Budget::new(Duration::from_secs(60)).cap(Duration::from_millis(5)),
+            Duration::from_millis(5)
+        );
+
+        let mut overruns = Overruns::default();
+        overruns.record(Duration::from_millis(3), &unlimited);
+        overruns.record(Duration::from_millis(3), &expired);
+        overruns.record(Duration::ZERO, &Budget::new(Duration::from_millis(1)));
+        assert_eq!(overruns.calls, 3);
+        assert_eq!(overruns.overruns, 1);
+        assert_eq!(overruns.worst, Duration::from_millis(3));
+    }
+}
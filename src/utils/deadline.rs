@@ -1,5 +1,32 @@
+use crate::parsers::Cli;
+use clap::ValueEnum;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
+/// The number of recent ticks the estimators look at.
+const WINDOW: usize = 32;
+
+/// The weight of the latest tick in the exponential moving average.
+const EMA_WEIGHT: f64 = 0.3;
+
+/// The percentile of recent tick durations used by the percentile estimator.
+const PERCENTILE: f64 = 0.9;
+
+/// The ways to estimate the duration of the next tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Estimator {
+    /// The mean duration of all ticks so far
+    Mean,
+    /// An exponential moving average of the tick durations
+    Ema,
+    /// The 90th percentile of the recent tick durations
+    Percentile,
+}
+
 /// A struct to manage deadlines for operations.
 pub struct Deadline {
     /// The start time of the operation.
@@ -8,11 +35,26 @@ pub struct Deadline {
     end: Instant,
     /// The number of ticks (iterations) that have occurred.
     ticks: usize,
+    /// The time of the last tick, or the start.
+    last_tick: Instant,
+    /// The durations of the most recent ticks.
+    recent: VecDeque<Duration>,
+    /// The exponential moving average of the tick durations, in seconds.
+    ema: Option<f64>,
+    /// How the duration of the next tick is estimated.
+    estimator: Estimator,
+    /// The fraction the estimated tick duration is increased by to be safe.
+    margin: f64,
+    /// The total time spent in every named phase of the ticks.
+    phases: Vec<(&'static str, Duration)>,
 }
 
 impl Deadline {
     /// Creates a new Deadline instance.
     ///
+    /// Predictions use an exponential moving average of the tick durations with a safety margin
+    /// of 20%.
+    ///
     /// # Arguments
     ///
     /// * `duration` - The duration of the deadline in milliseconds.
@@ -27,12 +69,38 @@ impl Deadline {
             start,
             end,
             ticks: 0,
+            last_tick: start,
+            recent: VecDeque::with_capacity(WINDOW),
+            ema: None,
+            estimator: Estimator::Ema,
+            margin: 0.2,
+            phases: Vec::new(),
         }
     }
 
+    /// Creates the deadline of a run from the command line arguments.
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self::new(cli.max_duration as f32)
+            .with_estimator(cli.deadline_estimator)
+            .with_margin(cli.safety_margin)
+    }
+
+    /// Sets how the duration of the next tick is estimated.
+    pub fn with_estimator(mut self, estimator: Estimator) -> Self {
+        self.estimator = estimator;
+        self
+    }
+
+    /// Sets the fraction the estimated tick duration is increased by, 0.2 for 20%.
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin.max(0.) as f64;
+        self
+    }
+
     /// Predicts if the next iteration will exceed the deadline.
     ///
-    /// This is useful if you want to terminate 1 iteration before the deadline.
+    /// This is useful if you want to terminate 1 iteration before the deadline. Before the
+    /// first tick there is nothing to predict from, so only a passed deadline counts.
     ///
     /// # Returns
     ///
@@ -46,26 +114,48 @@ impl Deadline {
             return true;
         }
 
-        // Return if not even half of the duration has passed
-        if (now - self.start) < ((self.end - self.start) / 2) {
+        let Some(estimate) = self.estimate() else {
             return false;
-        }
+        };
+        let predicted = estimate.mul_f64(1. + self.margin);
+        let remaining = self.end - now;
+        let will_exceed = predicted > remaining;
 
-        let elapsed = now - self.start;
-        let total_duration = self.end - self.start;
-        let avg_tick_duration = elapsed.div_f64(self.ticks as f64);
-        let will_exceed = avg_tick_duration > (total_duration - elapsed);
-
-        // WARNING: This is not guarranteed, it may still miscalculate and allow the deadline be
-        // missed
         if will_exceed {
-            log::debug!("No more iterations possible: elapsed: {:.2?}, total_allowed_duration: {:.2?}, tick_duration: {:.2?}, n_ticks: {:?}", 
-                elapsed, total_duration, avg_tick_duration, self.ticks)
+            log::debug!(
+                "No more iterations possible: elapsed: {:.2?}, remaining: {:.2?}, predicted tick duration: {:.2?}, n_ticks: {:?}",
+                now - self.start,
+                remaining,
+                predicted,
+                self.ticks
+            )
         }
 
         will_exceed
     }
 
+    /// Estimates the duration of the next tick, without the safety margin.
+    ///
+    /// # Returns
+    ///
+    /// The estimated duration, or `None` before the first tick.
+    pub fn estimate(&self) -> Option<Duration> {
+        if self.ticks == 0 {
+            return None;
+        }
+
+        match self.estimator {
+            Estimator::Mean => Some((self.last_tick - self.start).div_f64(self.ticks as f64)),
+            Estimator::Ema => self.ema.map(Duration::from_secs_f64),
+            Estimator::Percentile => {
+                let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+                sorted.sort();
+                let index = ((sorted.len() - 1) as f64 * PERCENTILE).ceil() as usize;
+                sorted.get(index).copied()
+            }
+        }
+    }
+
     /// Checks if the deadline has expired.
     ///
     /// # Returns
@@ -88,6 +178,154 @@ impl Deadline {
     ///
     /// This should be called at each iteration of the operation being timed.
     pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.record(now - self.last_tick);
+        self.last_tick = now;
+    }
+
+    /// Records the duration of a tick.
+    fn record(&mut self, duration: Duration) {
         self.ticks += 1;
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+
+        let seconds = duration.as_secs_f64();
+        self.ema = Some(match self.ema {
+            Some(ema) => EMA_WEIGHT * seconds + (1. - EMA_WEIGHT) * ema,
+            None => seconds,
+        });
+    }
+
+    /// Runs a named phase of a tick and adds its duration to the total of the phase.
+    ///
+    /// # Arguments
+    ///
+    /// * `phase` - The name of the phase, such as "plan" or "simulate".
+    /// * `f` - The work of the phase.
+    ///
+    /// # Returns
+    ///
+    /// The result of the work.
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        let elapsed = started.elapsed();
+
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+        result
+    }
+
+    /// Returns the total time spent in every phase, in the order the phases first ran.
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    /// Logs the total and mean time per tick spent in every phase.
+    pub fn log_phases(&self) {
+        for (phase, total) in &self.phases {
+            log::info!(
+                "Phase {}: {:.2?} in total, {:.2?} per tick",
+                phase,
+                total,
+                total.div_f64(self.ticks.max(1) as f64)
+            );
+        }
+    }
+}
+
+/// A watchdog that raises a flag once a deadline has passed.
+///
+/// Work in progress cannot be interrupted from outside in safe Rust, so the flag is checked
+/// cooperatively: planners that check their [`Budget`](crate::utils::Budget), such as ray
+/// casting between rays and the search based planners between iterations, give up at once,
+/// and the runner abandons the step in progress. The watchdog thread stops when the
+/// watchdog is dropped.
+pub struct Watchdog {
+    /// Whether the deadline has passed.
+    fired: Arc<AtomicBool>,
+    /// Stops the watchdog thread when dropped.
+    cancel: Option<mpsc::Sender<()>>,
+    /// The watchdog thread.
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Starts a watchdog for the deadline.
+    pub fn start(deadline: &Deadline) -> Self {
+        let fired = Arc::new(AtomicBool::new(false));
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let flag = Arc::clone(&fired);
+        let remaining = deadline.remaining();
+
+        let handle = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(remaining) {
+                log::debug!("Watchdog fired");
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+
+        Watchdog {
+            fired,
+            cancel: Some(cancel),
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns whether the deadline has passed.
+    pub fn fired(&self) -> bool {
+        self.fired.load(Ordering::Relaxed)
+    }
+
+    /// Returns the flag raised when the deadline passes.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.fired)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        drop(self.cancel.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the estimators and that nothing is predicted before the first tick.
+    #[test]
+    fn test_estimators() {
+        let mut deadline = Deadline::new(60_000.).with_estimator(Estimator::Percentile);
+        assert_eq!(deadline.estimate(), None);
+        assert!(!deadline.will_exceed_deadline());
+
+        for ms in [1, 1, 1, 1, 1, 1, 1, 1, 1, 20] {
+            deadline.record(Duration::from_millis(ms));
+        }
+        assert_eq!(deadline.estimate(), Some(Duration::from_millis(20)));
+
+        deadline.estimator = Estimator::Ema;
+        let ema = deadline.estimate().unwrap();
+        assert!(ema > Duration::from_millis(1) && ema < Duration::from_millis(20));
+
+        let expired = Deadline::new(0.);
+        assert!(expired.will_exceed_deadline());
+
+        let watchdog = Watchdog::start(&expired);
+        for _ in 0..100 {
+            if watchdog.fired() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(watchdog.fired(), "Watchdog fires once the deadline passed");
     }
 }
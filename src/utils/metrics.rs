@@ -0,0 +1,257 @@
+use crate::run::FleetResults;
+use crate::types::Grid;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The numbers a single drone is judged by.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DroneMetrics {
+    /// The id of the drone within its fleet.
+    pub id: usize,
+    /// The value the drone harvested.
+    pub reward: usize,
+    /// The number of distinct cells the drone stood on.
+    pub cells_visited: usize,
+    /// The number of steps that ended on a cell the drone had stood on before.
+    pub revisits: usize,
+    /// The number of moves straight back to the previous cell (A→B→A).
+    pub oscillations: usize,
+    /// The number of steps in which the drone harvested nothing.
+    pub idle_steps: usize,
+    /// The value harvested at every step the drone was active.
+    pub reward_per_step: Vec<usize>,
+}
+
+/// The numbers a run is judged by, computed from its results.
+///
+/// The metrics are computed from the positions and harvests the fleet recorded at every step,
+/// see `Fleet::end_step`, whatever the drones planned. Drones are lined up in time by their
+/// lifetimes, from the step they joined at until they leave. Positions are the cells the drones
+/// stood on at the start of every step, with the cell they ended the run on.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Metrics {
+    /// The value harvested by all drones together.
+    pub total_reward: usize,
+    /// The fraction of the cells holding a value at the start of the run that a drone visited.
+    pub coverage: f64,
+    /// The number of cells visited by more than one drone.
+    pub shared_cells: usize,
+    /// The fraction of the visited cells that were visited by more than one drone.
+    pub overlap: f64,
+    /// Jain's fairness index of the rewards of the drones, 1 when all harvested the same.
+    pub fairness: f64,
+    /// The smallest distance in cells between two drones during the run.
+    pub min_distance: Option<usize>,
+    /// The smallest distance in cells between two drones at every step, if two were flying.
+    pub min_distance_per_step: Vec<Option<usize>>,
+    /// The value harvested by all drones together at every step.
+    pub reward_per_step: Vec<usize>,
+    /// The numbers of every drone, indexed by drone id.
+    pub drones: Vec<DroneMetrics>,
+}
+
+impl Metrics {
+    /// Computes the metrics of a finished run.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid at the start of the run.
+    /// * `results` - What every drone did, see `Fleet::into_results`.
+    ///
+    /// # Returns
+    ///
+    /// The metrics of the run.
+    pub fn new(grid: &Grid, results: &FleetResults) -> Self {
+        let tracks = &results.positions;
+        let first_steps: Vec<usize> = (0..tracks.len())
+            .map(|drone| {
+                results
+                    .lifetimes
+                    .get(drone)
+                    .map_or(0, |lifetime| lifetime.joined.saturating_sub(1))
+            })
+            .collect();
+
+        let drones: Vec<DroneMetrics> = tracks
+            .iter()
+            .enumerate()
+            .map(|(id, track)| {
+                let rewards = results.harvests.get(id).cloned().unwrap_or_default();
+                let mut seen = HashSet::new();
+                let revisits = track.iter().filter(|&&cell| !seen.insert(cell)).count();
+                DroneMetrics {
+                    id,
+                    reward: rewards.iter().sum(),
+                    cells_visited: seen.len(),
+                    revisits,
+                    oscillations: track
+                        .windows(3)
+                        .filter(|moves| moves[0] == moves[2] && moves[0] != moves[1])
+                        .count(),
+                    idle_steps: rewards.iter().filter(|&&reward| reward == 0).count(),
+                    reward_per_step: rewards,
+                }
+            })
+            .collect();
+
+        // Number of drones that visited every cell
+        let mut visitors: HashMap<(usize, usize), usize> = HashMap::new();
+        for track in tracks {
+            for cell in track.iter().copied().collect::<HashSet<_>>() {
+                *visitors.entry(cell).or_default() += 1;
+            }
+        }
+        let rewarding = grid.data.iter().filter(|&&value| value > 0).count();
+        let covered = visitors
+            .keys()
+            .filter(|&&(x, y)| x < grid.size && y < grid.size && grid.value_at(x, y) > 0)
+            .count();
+        let shared_cells = visitors.values().filter(|&&drones| drones > 1).count();
+
+        let steps = drones
+            .iter()
+            .zip(&first_steps)
+            .map(|(drone, first)| first + drone.reward_per_step.len())
+            .max()
+            .unwrap_or(0);
+        let mut reward_per_step = vec![0; steps];
+        for (drone, &first) in drones.iter().zip(&first_steps) {
+            for (step, reward) in drone.reward_per_step.iter().enumerate() {
+                reward_per_step[first + step] += reward;
+            }
+        }
+        let min_distance_per_step: Vec<Option<usize>> = (0..steps)
+            .map(|step| {
+                let positions: Vec<(usize, usize)> = tracks
+                    .iter()
+                    .zip(&first_steps)
+                    .filter_map(|(track, &first)| track.get(step.checked_sub(first)?).copied())
+                    .collect();
+                closest(&positions)
+            })
+            .collect();
+
+        let total_reward = drones.iter().map(|drone| drone.reward).sum();
+        Metrics {
+            total_reward,
+            coverage: ratio(covered, rewarding),
+            shared_cells,
+            overlap: ratio(shared_cells, visitors.len()),
+            fairness: fairness(&drones),
+            min_distance: min_distance_per_step.iter().flatten().copied().min(),
+            min_distance_per_step,
+            reward_per_step,
+            drones,
+        }
+    }
+
+    /// Logs a summary of the metrics.
+    pub fn log(&self) {
+        log::info!(
+            "Reward {}, coverage {:.1}%, overlap {:.1}% ({} cells), fairness {:.3}",
+            self.total_reward,
+            self.coverage * 100.,
+            self.overlap * 100.,
+            self.shared_cells,
+            self.fairness
+        );
+        match self.min_distance {
+            Some(distance) => log::info!("Drones came within {} cells of each other", distance),
+            None => log::info!("No two drones flew at the same time"),
+        }
+        for drone in &self.drones {
+            log::info!(
+                "Drone {}: reward {}, {} cells, {} revisits, {} oscillations, {} idle steps",
+                drone.id,
+                drone.reward,
+                drone.cells_visited,
+                drone.revisits,
+                drone.oscillations,
+                drone.idle_steps
+            );
+        }
+    }
+}
+
+/// Returns the smallest Chebyshev distance between two of the positions.
+fn closest(positions: &[(usize, usize)]) -> Option<usize> {
+    positions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            positions[i + 1..]
+                .iter()
+                .map(move |b| a.0.abs_diff(b.0).max(a.1.abs_diff(b.1)))
+        })
+        .min()
+}
+
+/// Returns Jain's fairness index of the rewards of the drones, 1 for no or only empty rewards.
+fn fairness(drones: &[DroneMetrics]) -> f64 {
+    let sum: f64 = drones.iter().map(|drone| drone.reward as f64).sum();
+    let squares: f64 = drones
+        .iter()
+        .map(|drone| (drone.reward as f64).powi(2))
+        .sum();
+    if squares == 0. {
+        1.
+    } else {
+        sum * sum / (drones.len() as f64 * squares)
+    }
+}
+
+/// Returns `part / whole`, 0 if the whole is empty.
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run::Lifetime;
+
+    /// Tests the metrics of two drones, the second joining at the third step.
+    #[test]
+    fn test_run_metrics() {
+        let grid = Grid::new(vec![1, 1, 1, 0, 1, 1, 1, 1, 1]);
+        let results = FleetResults {
+            // Paths are what the drones planned, the metrics only follow the recorded steps
+            paths: vec![Vec::new(), Vec::new()],
+            rewards: vec![Vec::new(), Vec::new()],
+            lifetimes: vec![
+                Lifetime {
+                    joined: 0,
+                    left: None,
+                },
+                Lifetime {
+                    joined: 3,
+                    left: None,
+                },
+            ],
+            positions: vec![vec![(0, 0), (0, 1), (0, 0), (1, 1)], vec![(2, 2), (1, 1)]],
+            harvests: vec![vec![1, 0, 1], vec![0]],
+        };
+        let metrics = Metrics::new(&grid, &results);
+
+        let first = &metrics.drones[0];
+        assert_eq!(first.cells_visited, 3);
+        assert_eq!(
+            (first.revisits, first.oscillations, first.idle_steps),
+            (1, 1, 1)
+        );
+        assert_eq!(metrics.total_reward, 2);
+        assert_eq!(metrics.reward_per_step, vec![1, 0, 1]);
+        // The drones only fly together in the third step, from (0, 0) and (2, 2)
+        assert_eq!(metrics.min_distance_per_step, vec![None, None, Some(2)]);
+        assert_eq!(metrics.min_distance, Some(2));
+        // 4 of the 8 rewarding cells, (1, 1) shared by both drones
+        assert_eq!(metrics.coverage, 0.5);
+        assert_eq!(metrics.shared_cells, 1);
+        assert_eq!(metrics.overlap, 0.25);
+        assert_eq!(metrics.fairness, 0.5);
+    }
+}
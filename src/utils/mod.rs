@@ -1,4 +1,20 @@
+pub mod budget;
 pub mod deadline;
+pub mod metrics;
 pub mod plotter;
-pub use deadline::Deadline;
-pub use plotter::{create_path_traces, plot_paths, print_paths};
+pub mod raster;
+pub mod report;
+pub mod rng;
+pub mod svg;
+pub mod trajectory;
+pub mod tui;
+pub use budget::{Budget, Overruns};
+pub use deadline::{Deadline, Estimator, Watchdog};
+pub use metrics::{DroneMetrics, Metrics};
+pub use plotter::{create_path_traces, plot_paths, print_paths, Marker, Plot};
+pub use raster::{GifRecorder, Raster};
+pub use report::{DroneReport, GridSummary, Outcome, Report, REPORT_VERSION};
+pub use rng::Rng;
+pub use svg::Svg;
+pub use trajectory::TrajectoryCsv;
+pub use tui::Tui;
@@ -1,94 +1,288 @@
 use crate::types::{Grid, Path};
 use ::std::collections::VecDeque;
-use std::usize;
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use std::ops::Range;
 
-/// Plots the paths on the given grid and logs the result.
-///
-/// # Arguments
+/// The glyphs framing the trail cells of every drone, repeated for larger fleets.
+const GLYPHS: [char; 8] = ['*', '+', 'o', 'x', '#', '%', '=', '~'];
+
+/// The glyph framing cells on the trails of several drones.
+const SHARED: char = ':';
+
+/// The ANSI foreground colours of the drones, repeated for larger fleets.
+const COLOURS: [u8; 12] = [31, 32, 33, 34, 35, 36, 91, 92, 93, 94, 95, 96];
+
+/// The largest number of rows and columns plotted, larger grids are cropped.
+const MAX_VIEW: usize = 40;
+
+/// Where a drone is marked on its trail.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Marker {
+    /// The first cell of the trail
+    Start,
+    /// The last cell of the trail
+    #[default]
+    Current,
+}
+
+/// What a cell of the plot shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    /// The value of the cell.
+    Empty,
+    /// A cell on the trail of a single drone.
+    Trail(usize),
+    /// A cell on the trails of several drones.
+    Shared,
+    /// The marked position of a drone.
+    Drone(usize),
+}
+
+/// A text plot of a grid with the trail of every drone.
 ///
-/// * `grid` - The grid on which to plot the paths.
-/// * `paths` - A vector of vectors containing paths for each drone.
-pub fn plot_paths(grid: &Grid, paths: &Vec<Vec<Path>>) {
-    let mut output = String::new();
-    output.push('\n');
-
-    for i in 0..grid.size {
-        for j in 0..grid.size {
-            let value = grid.value_at(i, j);
-
-            let path_info = paths.iter().enumerate().find_map(|(idx, path)| {
-                path.last().and_then(|last_path| {
-                    let first_step = last_path.steps.front();
-                    let any_step = last_path.steps.iter().find(|&step| step == &(i, j));
-
-                    if first_step == Some(&(i, j)) {
-                        Some((idx, last_path.total_cost))
-                    } else if any_step.is_some() {
-                        Some((usize::MAX, last_path.total_cost))
-                    } else {
-                        None
-                    }
-                })
-            });
-            match path_info {
-                Some((p_idx, cost)) => {
-                    if p_idx != usize::MAX {
-                        output.push_str(&format!("{}X{:<3}", p_idx, cost));
-                    } else {
-                        output.push_str(&format!(" [{:2}]", value));
-                    }
+/// Every cell shows its value. Cells on the trail of a drone are framed by the glyph of that
+/// drone, cells on the trails of several drones by `:`, and every drone is marked as `D<id>` at
+/// the start or the end of its trail. Large grids are cropped to the trails, a legend lists
+/// the glyph, start, end and cost of every drone.
+pub struct Plot<'a> {
+    /// The grid whose values are shown.
+    grid: &'a Grid,
+    /// The paths of every drone, followed in order.
+    paths: &'a [Vec<Path>],
+    /// Where the drones are marked.
+    marker: Marker,
+    /// Whether drones are coloured with ANSI escape codes.
+    coloured: bool,
+    /// The rows and columns shown, fitted to the trails if not set.
+    view: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl<'a> Plot<'a> {
+    /// Creates the plot of a grid and the paths of the drones.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid whose values are shown.
+    /// * `paths` - The paths of every drone, their cells in order form the trail of the drone.
+    pub fn new(grid: &'a Grid, paths: &'a [Vec<Path>]) -> Self {
+        Plot {
+            grid,
+            paths,
+            marker: Marker::default(),
+            coloured: false,
+            view: None,
+        }
+    }
+
+    /// Marks the drones at the given end of their trails.
+    pub fn marking(mut self, marker: Marker) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    /// Colours the drones with ANSI escape codes.
+    pub fn coloured(mut self, coloured: bool) -> Self {
+        self.coloured = coloured;
+        self
+    }
+
+    /// Shows only the given rows and columns.
+    pub fn cropped(mut self, rows: Range<usize>, cols: Range<usize>) -> Self {
+        self.view = Some((rows, cols));
+        self
+    }
+
+    /// Renders the plot.
+    ///
+    /// Every cell of a trail is visited once, so the plot takes time linear in the size of the
+    /// view and the length of the paths.
+    ///
+    /// # Returns
+    ///
+    /// The plot with its legend, one line per row.
+    pub fn render(&self) -> String {
+        let size = self.grid.size;
+        let trails: Vec<Vec<(usize, usize)>> = self
+            .paths
+            .iter()
+            .map(|paths| {
+                let mut trail: Vec<(usize, usize)> = paths
+                    .iter()
+                    .flat_map(|path| path.steps.iter().copied())
+                    .collect();
+                trail.dedup();
+                trail.retain(|&(x, y)| x < size && y < size);
+                trail
+            })
+            .collect();
+        let (rows, cols) = self.view.clone().unwrap_or_else(|| self.fit(&trails));
+        let (rows, cols) = (
+            rows.start..rows.end.min(size),
+            cols.start..cols.end.min(size),
+        );
+        let width = cols.len();
+        let index = |(x, y): (usize, usize)| {
+            (rows.contains(&x) && cols.contains(&y))
+                .then(|| (x - rows.start) * width + y - cols.start)
+        };
+
+        let mut marks = vec![Mark::Empty; rows.len() * width];
+        for (drone, trail) in trails.iter().enumerate() {
+            for &cell in trail {
+                if let Some(i) = index(cell) {
+                    marks[i] = match marks[i] {
+                        Mark::Empty => Mark::Trail(drone),
+                        Mark::Trail(other) if other == drone => Mark::Trail(drone),
+                        _ => Mark::Shared,
+                    };
                 }
-                None => output.push_str(&format!("  {:2} ", value)),
             }
         }
-        output.push('\n');
+        for (drone, trail) in trails.iter().enumerate() {
+            if let Some(i) = self.mark(trail).and_then(index) {
+                marks[i] = Mark::Drone(drone);
+            }
+        }
+
+        let mut output = String::new();
+        if rows.len() < size || width < size {
+            output.push_str(&format!(
+                "Rows {}..{} and columns {}..{} of {}\n",
+                rows.start, rows.end, cols.start, cols.end, size
+            ));
+        }
+        for (x, row) in rows.clone().zip(marks.chunks(width.max(1))) {
+            for (y, mark) in cols.clone().zip(row) {
+                let value = self.grid.value_at(x, y);
+                let cell = match *mark {
+                    Mark::Empty => format!(" {:>3} ", value),
+                    Mark::Trail(drone) => {
+                        let glyph = GLYPHS[drone % GLYPHS.len()];
+                        self.paint(drone, format!("{}{:>3}{}", glyph, value, glyph), false)
+                    }
+                    Mark::Shared => format!("{}{:>3}{}", SHARED, value, SHARED),
+                    Mark::Drone(drone) => self.paint(drone, format!(" D{:<3}", drone), true),
+                };
+                output.push_str(&cell);
+            }
+            output.push('\n');
+        }
+
+        for (drone, trail) in trails.iter().enumerate() {
+            let cost: usize = self.paths[drone].iter().map(|path| path.total_cost).sum();
+            let line = match (trail.first(), trail.last()) {
+                (Some(start), Some(end)) => format!(
+                    "{} D{}: start {:?}, current {:?}, {} cells, cost {}",
+                    GLYPHS[drone % GLYPHS.len()],
+                    drone,
+                    start,
+                    end,
+                    trail.len(),
+                    cost
+                ),
+                _ => format!("{} D{}: no path", GLYPHS[drone % GLYPHS.len()], drone),
+            };
+            output.push_str(&self.paint(drone, line, false));
+            output.push('\n');
+        }
+        output.push_str(&format!("{} shared by several drones\n", SHARED));
+        output
+    }
+
+    /// Returns the marked cell of a trail.
+    fn mark(&self, trail: &[(usize, usize)]) -> Option<(usize, usize)> {
+        match self.marker {
+            Marker::Start => trail.first().copied(),
+            Marker::Current => trail.last().copied(),
+        }
+    }
+
+    /// Fits the view to the trails, centred on the first marked drone if they do not fit.
+    fn fit(&self, trails: &[Vec<(usize, usize)>]) -> (Range<usize>, Range<usize>) {
+        let size = self.grid.size;
+        if size <= MAX_VIEW {
+            return (0..size, 0..size);
+        }
+        let cells = trails.iter().flatten();
+        let (Some(top), Some(left)) = (
+            cells.clone().map(|cell| cell.0).min(),
+            cells.clone().map(|cell| cell.1).min(),
+        ) else {
+            return (0..MAX_VIEW, 0..MAX_VIEW);
+        };
+        let bottom = cells.clone().map(|cell| cell.0).max().unwrap_or(top);
+        let right = cells.map(|cell| cell.1).max().unwrap_or(left);
+        let centre = trails
+            .iter()
+            .find_map(|trail| self.mark(trail))
+            .unwrap_or((top, left));
+        let axis = |low: usize, high: usize, centre: usize| {
+            let (low, high) = (low.saturating_sub(2), (high + 3).min(size));
+            if high - low <= MAX_VIEW {
+                low..high
+            } else {
+                let start = centre.saturating_sub(MAX_VIEW / 2).min(size - MAX_VIEW);
+                start..start + MAX_VIEW
+            }
+        };
+        (axis(top, bottom, centre.0), axis(left, right, centre.1))
     }
 
-    log::info!("Paths on grid:\n{}", output);
+    /// Colours a text in the colour of a drone, if the plot is coloured.
+    fn paint(&self, drone: usize, text: String, bold: bool) -> String {
+        if !self.coloured {
+            return text;
+        }
+        let colour = COLOURS[drone % COLOURS.len()];
+        let style = if bold { "1;7;" } else { "" };
+        format!("\x1b[{}{}m{}\x1b[0m", style, colour, text)
+    }
 }
 
-/// Creates path traces from the given paths, positions, and final grid.
+/// Plots the paths on the given grid and logs the result.
+///
+/// Drones are marked at their current position and coloured if the log goes to a terminal,
+/// see [`Plot`] for other options.
 ///
 /// # Arguments
 ///
+/// * `grid` - The grid on which to plot the paths.
 /// * `paths` - A vector of vectors containing paths for each drone.
-/// * `positions` - A vector of initial positions for each drone.
-/// * `final_grid` - The final state of the grid.
+pub fn plot_paths(grid: &Grid, paths: &[Vec<Path>]) {
+    let plot = Plot::new(grid, paths).coloured(std::io::stderr().is_terminal());
+    log::info!("Paths on grid:\n{}", plot.render().trim_end());
+}
+
+/// Creates path traces from the given paths and the rewards collected following them.
+///
+/// Drones may join and leave during a run, so each trace starts at the position the drone
+/// joined at and ends at the last position it planned from. Drones that never planned get an
+/// empty trace. The total cost of a trace is the value the drone actually harvested, as
+/// reported by the simulator, so cells visited again only count what regrew in between.
+///
+/// # Arguments
+///
+/// * `paths` - A vector of vectors containing paths for each drone.
+/// * `rewards` - The value each drone harvested following each of its paths, see
+///   `Fleet::rewards`. Missing rewards count as nothing harvested.
 ///
 /// # Returns
 ///
 /// A vector of vectors containing path traces for each drone.
-pub fn create_path_traces(
-    paths: &Vec<Vec<Path>>,
-    positions: &Vec<(usize, usize)>,
-    final_grid: &Grid,
-) -> Vec<Vec<Path>> {
-    let mut path_traces: Vec<Vec<Path>> = vec![Vec::new(); positions.len()];
-
-    // Create initial path traces
-    for (index, path_vector) in paths.iter().enumerate() {
-        let mut current_drone_path = Path {
-            steps: VecDeque::<(usize, usize)>::new(),
-            total_cost: 0,
-        };
-        for path in path_vector.iter() {
-            current_drone_path
-                .steps
-                .push_back(*path.steps.front().unwrap());
-        }
-        path_traces[index] = vec![current_drone_path];
-    }
-
-    // Calculate total cost for each path trace
-    for path_trace in path_traces.iter_mut() {
-        let mut total_cost: usize = 0;
-        for (x, y) in &path_trace.first().unwrap().steps {
-            total_cost += final_grid.value_at(*x, *y) as usize;
-        }
-        path_trace.first_mut().unwrap().total_cost = total_cost;
-    }
-
-    path_traces
+pub fn create_path_traces(paths: &[Vec<Path>], rewards: &[Vec<usize>]) -> Vec<Vec<Path>> {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(drone, path_vector)| {
+            let steps: VecDeque<(usize, usize)> = path_vector
+                .iter()
+                .filter_map(|path| path.steps.front().copied())
+                .collect();
+            let total_cost = rewards.get(drone).map_or(0, |rewards| rewards.iter().sum());
+            vec![Path { steps, total_cost }]
+        })
+        .collect()
 }
 
 /// Prints the paths in a human-readable format.
@@ -96,7 +290,7 @@ pub fn create_path_traces(
 /// # Arguments
 ///
 /// * `paths` - A vector of vectors containing paths for each drone.
-pub fn print_paths(paths: &Vec<Vec<Path>>) {
+pub fn print_paths(paths: &[Vec<Path>]) {
     for (drone_index, drone_paths) in paths.iter().enumerate() {
         println!("Drone {}:", drone_index);
         for (path_index, path) in drone_paths.iter().enumerate() {
@@ -111,3 +305,48 @@ pub fn print_paths(paths: &Vec<Vec<Path>>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the glyphs of single and shared trails, the markers and cropping.
+    #[test]
+    fn test_plot_trails() {
+        let grid = Grid::new((0..16).collect());
+        let paths = vec![
+            vec![Path {
+                steps: VecDeque::from([(0, 0), (0, 1), (1, 1)]),
+                total_cost: 5,
+            }],
+            vec![Path {
+                steps: VecDeque::from([(2, 2), (1, 1)]),
+                total_cost: 0,
+            }],
+        ];
+        let plot = Plot::new(&grid, &paths).render();
+        let rows: Vec<&str> = plot.lines().collect();
+        assert_eq!(rows[0], "*  0**  1*   2    3 ");
+        assert_eq!(
+            rows[1], "   4  D1     6    7 ",
+            "Later drones are marked on top"
+        );
+        assert_eq!(rows[2], "   8    9 + 10+  11 ");
+        assert_eq!(
+            rows[4],
+            "* D0: start (0, 0), current (1, 1), 3 cells, cost 5"
+        );
+
+        let plot = Plot::new(&grid, &paths)
+            .marking(Marker::Start)
+            .cropped(0..2, 1..3)
+            .render();
+        let rows: Vec<&str> = plot.lines().collect();
+        assert_eq!(rows[0], "Rows 0..2 and columns 1..3 of 4");
+        assert_eq!(rows[1], "*  1*   2 ");
+        assert_eq!(rows[2], ":  5:   6 ");
+
+        let coloured = Plot::new(&grid, &paths).coloured(true).render();
+        assert!(coloured.contains("\x1b[1;7;32m D1  \x1b[0m"));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::run::Fleet;
+use crate::traits::Observer;
+use crate::types::{Grid, Obstacle, Path};
+use crate::utils::svg::{colour_rgb, heat_rgb, PALETTE};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The number of colours of the heatmap, the first entries of the palette.
+const HEAT_LEVELS: usize = 64;
+
+/// The palette index of the first drone colour.
+const DRONES: u8 = HEAT_LEVELS as u8;
+
+/// The palette index of obstacles.
+const OBSTACLE: u8 = DRONES + PALETTE.len() as u8;
+
+/// The palette index of the outline of the drones.
+const OUTLINE: u8 = OBSTACLE + 1;
+
+/// The target width of an image, in pixels.
+const TARGET: usize = 600;
+
+/// A raster image of a grid with the trajectories of the drones.
+///
+/// The grid is drawn as a heatmap, every trajectory as a trail of cells in the colour of its
+/// drone and the position of every drone as a full cell with a white outline. All images share
+/// a single palette, so frames of a run can be written as an animation without quantising them.
+/// Large grids are averaged into blocks of cells per pixel.
+pub struct Raster<'a> {
+    /// The grid drawn as a heatmap.
+    grid: &'a Grid,
+    /// The path traces of every drone.
+    paths: &'a [Vec<Path>],
+    /// The obstacles to draw.
+    obstacles: &'a [Obstacle],
+    /// The drones to mark and their positions, the ends of their traces if not set.
+    positions: Option<&'a [(usize, (usize, usize))]>,
+}
+
+impl<'a> Raster<'a> {
+    /// Creates the image of a grid and the trajectories of the drones.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid drawn as a heatmap.
+    /// * `paths` - The path traces of every drone, see `create_path_traces`.
+    pub fn new(grid: &'a Grid, paths: &'a [Vec<Path>]) -> Self {
+        Raster {
+            grid,
+            paths,
+            obstacles: &[],
+            positions: None,
+        }
+    }
+
+    /// Draws the obstacles on top of the grid.
+    pub fn with_obstacles(mut self, obstacles: &'a [Obstacle]) -> Self {
+        self.obstacles = obstacles;
+        self
+    }
+
+    /// Marks the given drones at the given positions instead of at the ends of their traces.
+    pub fn with_positions(mut self, positions: &'a [(usize, (usize, usize))]) -> Self {
+        self.positions = Some(positions);
+        self
+    }
+
+    /// Returns the width and height of the image in pixels.
+    pub fn side(&self) -> usize {
+        layout(self.grid.size).2
+    }
+
+    /// Renders the image.
+    ///
+    /// # Returns
+    ///
+    /// The palette index of every pixel, row by row, see [`palette`].
+    pub fn render(&self) -> Vec<u8> {
+        let size = self.grid.size;
+        let (block, scale, side) = layout(size);
+        let mut pixels = vec![0; side * side];
+        let mut fill = |(x, y): (usize, usize), inset: usize, colour: u8| {
+            // Positions off the grid have no pixels to draw on
+            if x >= size || y >= size {
+                return;
+            }
+            let (row, col) = (x / block * scale, y / block * scale);
+            for r in row + inset..row + scale - inset {
+                pixels[r * side + col + inset..r * side + col + scale - inset].fill(colour);
+            }
+        };
+
+        // Heatmap, averaged over blocks of cells
+        let max = self.grid.data.iter().copied().max().unwrap_or(0).max(1) as f64;
+        for row in (0..size).step_by(block) {
+            for col in (0..size).step_by(block) {
+                let rows = row..(row + block).min(size);
+                let cols = col..(col + block).min(size);
+                let cells = rows.len() * cols.len();
+                let total: usize = rows
+                    .flat_map(|x| cols.clone().map(move |y| (x, y)))
+                    .map(|(x, y)| self.grid.value_at(x, y) as usize)
+                    .sum();
+                let level = total as f64 / cells as f64 / max * (HEAT_LEVELS - 1) as f64;
+                fill((row, col), 0, level.round() as u8);
+            }
+        }
+
+        for obstacle in self.obstacles {
+            for x in obstacle.x..(obstacle.x + obstacle.height).min(size) {
+                for y in obstacle.y..(obstacle.y + obstacle.width).min(size) {
+                    fill((x, y), 0, OBSTACLE);
+                }
+            }
+        }
+
+        let trail = scale / 4;
+        let mut ends = Vec::new();
+        for (drone, traces) in self.paths.iter().enumerate() {
+            let mut end = None;
+            for cell in traces.iter().flat_map(|trace| trace.steps.iter().copied()) {
+                fill(cell, trail, colour(drone));
+                end = Some(cell);
+            }
+            if let Some(end) = end {
+                ends.push((drone, end));
+            }
+        }
+
+        for &(drone, cell) in self.positions.unwrap_or(&ends) {
+            fill(cell, 0, if scale >= 4 { OUTLINE } else { colour(drone) });
+            fill(cell, (scale >= 4) as usize, colour(drone));
+        }
+        pixels
+    }
+
+    /// Renders the image and writes it to a PNG file.
+    pub fn save_png(&self, path: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("Failed to create: {}", path))?;
+        self.write_png(BufWriter::new(file))
+            .with_context(|| format!("Failed to write the image: {}", path))
+    }
+
+    /// Renders the image and writes it as a PNG to the given writer.
+    pub fn write_png(&self, out: impl Write) -> Result<()> {
+        let side = self.side() as u32;
+        let mut encoder = png::Encoder::new(out, side, side);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(palette());
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.render())?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// An observer that records a run as an animated GIF, one frame per step.
+///
+/// Every frame shows the grid at the end of the step, the trail of every drone and the position
+/// of the active drones. Frames are written while the simulation runs, so long runs are not held
+/// in memory.
+pub struct GifRecorder<W: Write> {
+    /// The encoder frames are written to, taken when the animation is finished.
+    encoder: Option<gif::Encoder<W>>,
+    /// The time every frame is shown, in hundredths of a second.
+    delay: u16,
+    /// The obstacles drawn on every frame.
+    obstacles: Vec<Obstacle>,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    /// Creates the GIF file at the given path, see [`GifRecorder::new`].
+    pub fn create(path: &str, grid: &Grid, delay_ms: u64, obstacles: &[Obstacle]) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create animation: {}", path))?;
+        Self::new(BufWriter::new(file), grid, delay_ms, obstacles)
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Writes the header and the starting grid as the first frame to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Where the animation is written to.
+    /// * `grid` - The grid at the start of the run.
+    /// * `delay_ms` - The time every frame is shown, in ms, rounded to hundredths of a second.
+    /// * `obstacles` - The obstacles drawn on every frame.
+    pub fn new(out: W, grid: &Grid, delay_ms: u64, obstacles: &[Obstacle]) -> Result<Self> {
+        let side = layout(grid.size).2 as u16;
+        let mut encoder = gif::Encoder::new(out, side, side, &palette())?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        let mut recorder = GifRecorder {
+            encoder: Some(encoder),
+            delay: (delay_ms / 10).clamp(1, u16::MAX as u64) as u16,
+            obstacles: obstacles.to_vec(),
+        };
+        let raster = Raster::new(grid, &[]).with_obstacles(obstacles);
+        recorder.write_frame(raster.side(), raster.render())?;
+        Ok(recorder)
+    }
+
+    /// Appends a rendered image as a frame.
+    fn write_frame(&mut self, side: usize, pixels: Vec<u8>) -> Result<()> {
+        let Some(encoder) = &mut self.encoder else {
+            anyhow::bail!("The animation is already finished");
+        };
+        let side = side as u16;
+        let mut frame = gif::Frame::from_indexed_pixels(side, side, pixels, None);
+        frame.delay = self.delay;
+        encoder.write_frame(&frame)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Observer for GifRecorder<W> {
+    fn on_step(&mut self, _step: usize, grid: &Grid, fleet: &Fleet) -> Result<()> {
+        let trails: Vec<Vec<Path>> = fleet
+            .paths()
+            .iter()
+            .map(|paths| {
+                vec![Path {
+                    steps: paths
+                        .iter()
+                        .filter_map(|path| path.steps.front())
+                        .copied()
+                        .collect(),
+                    total_cost: 0,
+                }]
+            })
+            .collect();
+        let positions: Vec<(usize, (usize, usize))> = fleet
+            .active()
+            .into_iter()
+            .map(|(drone, state)| (drone, state.position))
+            .collect();
+        let raster = Raster::new(grid, &trails)
+            .with_obstacles(&self.obstacles)
+            .with_positions(&positions);
+        let (side, pixels) = (raster.side(), raster.render());
+        self.write_frame(side, pixels)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.into_inner()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the palette shared by all images, as red, green and blue triples.
+///
+/// The heatmap comes first, from the lowest to the highest value, followed by the colours of
+/// the drones, the colour of obstacles and the outline of the drones.
+pub fn palette() -> Vec<u8> {
+    let heat = (0..HEAT_LEVELS).map(|level| heat_rgb(level as f64 / (HEAT_LEVELS - 1) as f64));
+    let drones = (0..PALETTE.len()).map(colour_rgb);
+    heat.chain(drones)
+        .chain([(0x33, 0x33, 0x33), (0xff, 0xff, 0xff)])
+        .flat_map(|(r, g, b)| [r, g, b])
+        .collect()
+}
+
+/// Returns the palette index of the colour of a drone.
+fn colour(drone: usize) -> u8 {
+    DRONES + (drone % PALETTE.len()) as u8
+}
+
+/// Lays out a grid as an image.
+///
+/// # Returns
+///
+/// The number of cells per block, the pixels per block and the width of the image in pixels.
+fn layout(size: usize) -> (usize, usize, usize) {
+    let block = size.div_ceil(TARGET).max(1);
+    let blocks = size.div_ceil(block).max(1);
+    let scale = (TARGET / blocks).max(1);
+    (block, scale, blocks * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Tests the pixels of a PNG and the number of frames of a GIF of a tiny run.
+    #[test]
+    fn test_png_and_gif() {
+        let grid = Grid::new(vec![0, 10, 20, 30, 40, 50, 60, 70, 80]);
+        let paths = vec![vec![Path {
+            steps: VecDeque::from([(0, 0), (1, 1)]),
+            total_cost: 0,
+        }]];
+        let mut png = Vec::new();
+        Raster::new(&grid, &paths).write_png(&mut png).unwrap();
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let side = TARGET / 3 * 3;
+        assert_eq!(
+            (reader.info().width, reader.info().height),
+            (side as u32, side as u32)
+        );
+        let at = |x: usize, y: usize| pixels[x * side + y];
+        let cell = side / 3;
+        assert_eq!(
+            at(cell / 2, cell / 2),
+            colour(0),
+            "Trail in the drone colour"
+        );
+        assert_eq!(at(0, 0), 0, "Lowest value at the trail edge");
+        assert_eq!(at(side - 1, side - 1), HEAT_LEVELS as u8 - 1);
+        assert_eq!(at(cell, cell), OUTLINE, "End marked with an outline");
+
+        let mut gif = Vec::new();
+        let mut recorder = GifRecorder::new(&mut gif, &grid, 100, &[]).unwrap();
+        let mut fleet = Fleet::new(&[(0, 0)]);
+        fleet.push_path(0, paths[0][0].clone());
+        recorder.on_step(1, &grid, &fleet).unwrap();
+        recorder.finish().unwrap();
+        drop(recorder);
+
+        let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 10);
+            frames += 1;
+        }
+        assert_eq!(frames, 2, "The starting grid and a frame per step");
+    }
+
+    /// Tests that traces and positions off the grid are left out instead of panicking.
+    #[test]
+    fn test_render_skips_positions_off_grid() {
+        let grid = Grid::new(vec![0, 10, 20, 30, 40, 50, 60, 70, 80]);
+        let on_grid = vec![vec![Path {
+            steps: VecDeque::from([(0, 0), (1, 1)]),
+            total_cost: 0,
+        }]];
+        let off_grid = vec![vec![Path {
+            steps: VecDeque::from([(0, 0), (1, 1), (3, 1), (1, 7)]),
+            total_cost: 0,
+        }]];
+
+        let expected = Raster::new(&grid, &on_grid)
+            .with_positions(&[(0, (1, 1))])
+            .render();
+        let rendered = Raster::new(&grid, &off_grid)
+            .with_positions(&[(0, (1, 1)), (1, (5, 5))])
+            .render();
+        assert_eq!(rendered, expected);
+    }
+}
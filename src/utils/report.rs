@@ -0,0 +1,181 @@
+use crate::types::{Grid, Path};
+use crate::utils::Metrics;
+use serde::Serialize;
+
+/// The version of the report schema, increased whenever a field changes meaning or is removed.
+pub const REPORT_VERSION: u32 = 1;
+
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    /// All time steps were run.
+    Completed,
+    /// The run stopped early to keep to its deadline, or lost steps to overruns in the
+    /// real-time runtime.
+    Stopped,
+    /// The run failed.
+    Failed {
+        /// What went wrong.
+        error: String,
+    },
+}
+
+/// The grid a run took place on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct GridSummary {
+    /// The name of the grid.
+    pub name: String,
+    /// The number of rows and columns.
+    pub size: usize,
+    /// The sum of the values of all cells at the start of the run.
+    pub total_value: usize,
+}
+
+/// What a single drone did during a run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DroneReport {
+    /// The id of the drone within its fleet.
+    pub id: usize,
+    /// The position of the drone at every step it took part in.
+    pub steps: Vec<(usize, usize)>,
+    /// The value harvested at every step, as reported by the simulator.
+    pub rewards: Vec<usize>,
+    /// The sum of the rewards.
+    pub total_reward: usize,
+}
+
+/// The results of a run in a stable, versioned schema.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    /// The version of the schema, see [`REPORT_VERSION`].
+    pub version: u32,
+    /// How the run ended.
+    pub outcome: Outcome,
+    /// The number of time steps asked for.
+    pub time_steps: usize,
+    /// The number of time steps run.
+    pub steps: usize,
+    /// The grid the run took place on.
+    pub grid: GridSummary,
+    /// What every drone did, indexed by drone id.
+    pub drones: Vec<DroneReport>,
+    /// The reward collected by all drones together.
+    pub total_reward: usize,
+    /// The metrics of the run, `null` if it failed.
+    pub metrics: Option<Metrics>,
+}
+
+impl Report {
+    /// Creates the report of a run.
+    ///
+    /// The reward of a step is the value the drone harvested, as in the totals of the path
+    /// traces.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the grid.
+    /// * `grid` - The grid at the start of the run.
+    /// * `path_traces` - The path traces of every drone, see `create_path_traces`.
+    /// * `rewards` - The value every drone harvested at every step, see `Fleet::rewards`.
+    /// * `time_steps` - The number of time steps asked for.
+    /// * `steps` - The number of time steps run.
+    /// * `outcome` - How the run ended.
+    ///
+    /// # Returns
+    ///
+    /// The report of the run.
+    pub fn new(
+        name: &str,
+        grid: &Grid,
+        path_traces: &[Vec<Path>],
+        rewards: &[Vec<usize>],
+        time_steps: usize,
+        steps: usize,
+        outcome: Outcome,
+    ) -> Self {
+        let drones: Vec<DroneReport> = path_traces
+            .iter()
+            .enumerate()
+            .map(|(id, traces)| {
+                let steps: Vec<(usize, usize)> = traces
+                    .iter()
+                    .flat_map(|trace| trace.steps.iter().copied())
+                    .collect();
+                let rewards = rewards.get(id).cloned().unwrap_or_default();
+                DroneReport {
+                    id,
+                    total_reward: rewards.iter().sum(),
+                    steps,
+                    rewards,
+                }
+            })
+            .collect();
+
+        Report {
+            version: REPORT_VERSION,
+            outcome,
+            time_steps,
+            steps,
+            grid: GridSummary {
+                name: name.to_string(),
+                size: grid.size,
+                total_value: grid.data.iter().map(|&value| value as usize).sum(),
+            },
+            total_reward: drones.iter().map(|drone| drone.total_reward).sum(),
+            drones,
+            metrics: None,
+        }
+    }
+
+    /// Adds the metrics of the run, see [`Metrics::new`].
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Serialises the report as JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_path_traces;
+    use std::collections::VecDeque;
+
+    /// Tests the rewards and the field names of the schema.
+    #[test]
+    fn test_report_schema() {
+        let grid = Grid::new(vec![1, 2, 3, 4]);
+        let paths = vec![vec![
+            Path {
+                steps: VecDeque::from([(0, 0), (0, 1)]),
+                total_cost: 0,
+            },
+            Path {
+                steps: VecDeque::from([(0, 1), (1, 1)]),
+                total_cost: 0,
+            },
+        ]];
+        // The second cell was harvested by another drone first, so nothing was left
+        let rewards = vec![vec![1, 0]];
+        let traces = create_path_traces(&paths, &rewards);
+        assert_eq!(traces[0][0].total_cost, 1);
+        let report = Report::new("tiny", &grid, &traces, &rewards, 4, 2, Outcome::Stopped);
+
+        assert_eq!(report.drones[0].steps, vec![(0, 0), (0, 1)]);
+        assert_eq!(report.drones[0].rewards, vec![1, 0]);
+        assert_eq!(report.total_reward, 1);
+        assert_eq!(report.grid.total_value, 10);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report.to_json().unwrap()).expect("Invalid JSON");
+        assert_eq!(json["version"], REPORT_VERSION);
+        assert_eq!(json["outcome"]["status"], "stopped");
+        assert_eq!(json["drones"][0]["steps"][1], serde_json::json!([0, 1]));
+        assert!(json["metrics"].is_null());
+    }
+}
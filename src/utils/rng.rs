@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// A small, seedable pseudo random number generator (SplitMix64).
+///
+/// The generator is deterministic for a given seed and its whole state is a single `u64`, so
+/// runs can be reproduced exactly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rng {
+    /// The internal state of the generator.
+    pub state: u64,
+}
+
+impl Rng {
+    /// Creates a new generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Returns the next random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a random index in `0..n`, `n` must be larger than 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a random integer in `-max..=max`.
+    pub fn symmetric(&mut self, max: u8) -> i16 {
+        let span = 2 * max as u64 + 1;
+        (self.next_u64() % span) as i16 - max as i16
+    }
+}
@@ -0,0 +1,311 @@
+use crate::types::{Grid, Obstacle, Path};
+use anyhow::{Context, Result};
+use std::fmt::Write;
+
+/// The colours of the drones, repeated for fleets larger than the palette.
+pub const PALETTE: [&str; 10] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#bfef45",
+    "#fabed4", "#469990",
+];
+
+/// The colours of the heatmap from the lowest to the highest value (viridis).
+const HEATMAP: [(u8, u8, u8); 5] = [
+    (68, 1, 84),
+    (59, 82, 139),
+    (33, 145, 140),
+    (94, 201, 98),
+    (253, 231, 37),
+];
+
+/// The width of the grid in the figure, in pixels.
+const WIDTH: f64 = 800.;
+
+/// The maximum number of heatmap blocks per row, larger grids are averaged into blocks.
+const MAX_BLOCKS: usize = 100;
+
+/// The height of a line of the legend, in pixels.
+const LEGEND_LINE: f64 = 20.;
+
+/// An SVG figure of a grid with the trajectories of the drones.
+///
+/// The grid is drawn as a heatmap, every trajectory as a line in the colour of its drone with a
+/// circle at the start and a square at the end. Obstacles and the territories of the drones can
+/// be drawn on top. Large grids are averaged into blocks, so the figure stays small enough to be
+/// embedded in a report.
+pub struct Svg<'a> {
+    /// The grid drawn as a heatmap.
+    grid: &'a Grid,
+    /// The path traces of every drone.
+    paths: &'a [Vec<Path>],
+    /// The obstacles to draw.
+    obstacles: &'a [Obstacle],
+    /// The drone owning every cell, if territories are drawn.
+    territories: Option<&'a [Option<usize>]>,
+}
+
+impl<'a> Svg<'a> {
+    /// Creates the figure of a grid and the trajectories of the drones.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The grid drawn as a heatmap.
+    /// * `paths` - The path traces of every drone, see `create_path_traces`.
+    pub fn new(grid: &'a Grid, paths: &'a [Vec<Path>]) -> Self {
+        Svg {
+            grid,
+            paths,
+            obstacles: &[],
+            territories: None,
+        }
+    }
+
+    /// Draws the obstacles on top of the grid.
+    pub fn with_obstacles(mut self, obstacles: &'a [Obstacle]) -> Self {
+        self.obstacles = obstacles;
+        self
+    }
+
+    /// Tints every cell in the colour of the drone owning it, indexed like the grid data.
+    pub fn with_territories(mut self, territories: &'a [Option<usize>]) -> Self {
+        self.territories = Some(territories);
+        self
+    }
+
+    /// Renders the figure.
+    ///
+    /// # Returns
+    ///
+    /// The SVG document.
+    pub fn render(&self) -> String {
+        let size = self.grid.size.max(1);
+        let scale = WIDTH / size as f64;
+        let height = WIDTH + LEGEND_LINE * (self.paths.len() as f64 + 1.);
+        let mut svg = String::new();
+
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = WIDTH,
+            h = height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{}" height="{}" fill="white"/>"#,
+            WIDTH, height
+        );
+        self.heatmap(&mut svg, scale);
+
+        if let Some(territories) = self.territories {
+            let _ = writeln!(svg, r#"<g fill-opacity="0.35">"#);
+            for (index, owner) in territories.iter().enumerate() {
+                let Some(drone) = owner else {
+                    continue;
+                };
+                let (x, y) = (index / size, index % size);
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+                    y as f64 * scale,
+                    x as f64 * scale,
+                    scale,
+                    scale,
+                    colour(*drone)
+                );
+            }
+            let _ = writeln!(svg, "</g>");
+        }
+
+        for obstacle in self.obstacles {
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="#333333" stroke="black"/>"##,
+                obstacle.y as f64 * scale,
+                obstacle.x as f64 * scale,
+                obstacle.width as f64 * scale,
+                obstacle.height as f64 * scale
+            );
+        }
+
+        let stroke = (scale / 4.).clamp(1., 6.);
+        let marker = (scale / 2.5).clamp(3., 12.);
+        for (drone, traces) in self.paths.iter().enumerate() {
+            let cells: Vec<(usize, usize)> = traces
+                .iter()
+                .flat_map(|trace| trace.steps.iter().copied())
+                .collect();
+            let (Some(&start), Some(&end)) = (cells.first(), cells.last()) else {
+                continue;
+            };
+            let centre =
+                |(x, y): (usize, usize)| ((y as f64 + 0.5) * scale, (x as f64 + 0.5) * scale);
+
+            let points: Vec<String> = cells
+                .iter()
+                .map(|&cell| {
+                    let (cx, cy) = centre(cell);
+                    format!("{:.2},{:.2}", cx, cy)
+                })
+                .collect();
+            let _ = writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{:.2}" stroke-linejoin="round" stroke-linecap="round" stroke-opacity="0.9"/>"#,
+                points.join(" "),
+                colour(drone),
+                stroke
+            );
+
+            let (sx, sy) = centre(start);
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}" stroke="white"/>"#,
+                sx,
+                sy,
+                marker / 2.,
+                colour(drone)
+            );
+            let (ex, ey) = centre(end);
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" stroke="white"/>"#,
+                ex - marker / 2.,
+                ey - marker / 2.,
+                marker,
+                marker,
+                colour(drone)
+            );
+        }
+
+        self.legend(&mut svg);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders the figure and writes it to a file.
+    pub fn save(&self, path: &str) -> Result<()> {
+        std::fs::write(path, self.render())
+            .with_context(|| format!("Failed to write the figure: {}", path))
+    }
+
+    /// Draws the grid as a heatmap, averaging large grids into blocks.
+    fn heatmap(&self, svg: &mut String, scale: f64) {
+        let size = self.grid.size;
+        let block = size.div_ceil(MAX_BLOCKS).max(1);
+        let max = self.grid.data.iter().copied().max().unwrap_or(0).max(1) as f64;
+
+        let _ = writeln!(svg, r#"<g shape-rendering="crispEdges">"#);
+        for row in (0..size).step_by(block) {
+            for col in (0..size).step_by(block) {
+                let rows = row..(row + block).min(size);
+                let cols = col..(col + block).min(size);
+                let cells = rows.len() * cols.len();
+                let total: usize = rows
+                    .flat_map(|x| cols.clone().map(move |y| (x, y)))
+                    .map(|(x, y)| self.grid.value_at(x, y) as usize)
+                    .sum();
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+                    col as f64 * scale,
+                    row as f64 * scale,
+                    block as f64 * scale,
+                    block as f64 * scale,
+                    heat(total as f64 / cells as f64 / max)
+                );
+            }
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+
+    /// Draws a legend with the colour of every drone below the grid.
+    fn legend(&self, svg: &mut String) {
+        let _ = writeln!(svg, r#"<g font-family="sans-serif" font-size="14">"#);
+        for drone in 0..self.paths.len() {
+            let y = WIDTH + LEGEND_LINE * (drone as f64 + 0.5);
+            let _ = writeln!(
+                svg,
+                r#"<rect x="10" y="{:.2}" width="12" height="12" fill="{}"/><text x="30" y="{:.2}">Drone {}</text>"#,
+                y,
+                colour(drone),
+                y + 11.,
+                drone
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+}
+
+/// Returns the colour of a drone.
+pub fn colour(drone: usize) -> &'static str {
+    PALETTE[drone % PALETTE.len()]
+}
+
+/// Returns the colour of a drone as red, green and blue.
+pub(crate) fn colour_rgb(drone: usize) -> (u8, u8, u8) {
+    let hex = colour(drone);
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).unwrap_or(0);
+    (channel(1), channel(3), channel(5))
+}
+
+/// Returns the heatmap colour of a value between 0 and 1.
+fn heat(value: f64) -> String {
+    let (r, g, b) = heat_rgb(value);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Returns the heatmap colour of a value between 0 and 1 as red, green and blue.
+pub(crate) fn heat_rgb(value: f64) -> (u8, u8, u8) {
+    let position = value.clamp(0., 1.) * (HEATMAP.len() - 1) as f64;
+    let index = (position.floor() as usize).min(HEATMAP.len() - 2);
+    let t = position - index as f64;
+    let (from, to) = (HEATMAP[index], HEATMAP[index + 1]);
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Tests that the figure holds a heatmap cell per grid cell, the trajectory and its markers.
+    #[test]
+    fn test_render_svg() {
+        let grid = Grid::new(vec![0, 10, 20, 30, 40, 50, 60, 70, 80]);
+        let paths = vec![vec![Path {
+            steps: VecDeque::from([(0, 0), (1, 1), (2, 1)]),
+            total_cost: 0,
+        }]];
+        let obstacles = [Obstacle {
+            x: 0,
+            y: 2,
+            height: 1,
+            width: 1,
+        }];
+        let territories = vec![Some(0); 9];
+        let svg = Svg::new(&grid, &paths)
+            .with_obstacles(&obstacles)
+            .with_territories(&territories)
+            .render();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        let scale = WIDTH / 3.;
+        let points = format!(
+            "{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}",
+            0.5 * scale,
+            0.5 * scale,
+            1.5 * scale,
+            1.5 * scale,
+            1.5 * scale,
+            2.5 * scale
+        );
+        assert!(
+            svg.contains(&points),
+            "The trajectory goes through the cell centres"
+        );
+        assert!(svg.contains(&heat(0.)) && svg.contains(&heat(1.)));
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert_eq!(svg.matches("#333333").count(), 1);
+        assert_eq!(heat(0.), "#440154");
+    }
+}
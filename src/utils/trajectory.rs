@@ -0,0 +1,119 @@
+use crate::traits::Observer;
+use crate::types::Move;
+use anyhow::{Context, Result};
+use std::cmp::Ordering::{Equal, Greater, Less};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The header of the trajectory CSV.
+const HEADER: &str = "step,drone,x,y,action,next_x,next_y,reward,value_before,value_after,score";
+
+/// An observer that writes the trajectory of every drone as CSV, one row per step and drone.
+///
+/// Rows are written while the simulation runs, so long runs are not held in memory. Every row
+/// holds the position of the drone at the start of the step, the direction it moved in, where
+/// it ended up, the value it harvested in the move, the value of the cell it left before and
+/// after the move, and the score the planner gave the path.
+pub struct TrajectoryCsv<W: Write> {
+    /// Where the rows are written to.
+    out: W,
+}
+
+impl TrajectoryCsv<BufWriter<File>> {
+    /// Creates the CSV file at the given path and writes the header.
+    pub fn create(path: &str) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create trajectory: {}", path))?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write> TrajectoryCsv<W> {
+    /// Writes the header to the given writer.
+    pub fn new(mut out: W) -> Result<Self> {
+        writeln!(out, "{}", HEADER)?;
+        Ok(TrajectoryCsv { out })
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Observer for TrajectoryCsv<W> {
+    fn on_move(&mut self, event: &Move) -> Result<()> {
+        let (x, y) = event.from;
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            event.step,
+            event.drone,
+            x,
+            y,
+            action(event.from, event.to),
+            event.to.0,
+            event.to.1,
+            event.reward,
+            event.before.value_at(x, y),
+            event.after.value_at(x, y),
+            event.path.total_cost
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Names the direction of a move as a compass point, rows increasing to the south.
+pub(crate) fn action(from: (usize, usize), to: (usize, usize)) -> &'static str {
+    match (to.0.cmp(&from.0), to.1.cmp(&from.1)) {
+        (Less, Less) => "NW",
+        (Less, Equal) => "N",
+        (Less, Greater) => "NE",
+        (Equal, Less) => "W",
+        (Equal, Equal) => "stay",
+        (Equal, Greater) => "E",
+        (Greater, Less) => "SW",
+        (Greater, Equal) => "S",
+        (Greater, Greater) => "SE",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Grid, Path};
+    use std::collections::VecDeque;
+
+    /// Tests that a move is written as a single row below the header.
+    #[test]
+    fn test_trajectory_rows() {
+        let before = Grid::new(vec![9, 2, 3, 4]);
+        let after = Grid::new(vec![0, 3, 3, 4]);
+        let path = Path {
+            steps: VecDeque::from([(0, 0), (1, 1)]),
+            total_cost: 7,
+        };
+        let mut csv = TrajectoryCsv::new(Vec::new()).unwrap();
+        csv.on_move(&Move {
+            step: 1,
+            drone: 0,
+            from: (0, 0),
+            to: (1, 1),
+            path: &path,
+            reward: 9,
+            before: &before,
+            after: &after,
+        })
+        .unwrap();
+        csv.finish().unwrap();
+
+        let text = String::from_utf8(csv.into_inner()).unwrap();
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows, vec![HEADER, "1,0,0,0,SE,1,1,9,9,0,7"]);
+    }
+}
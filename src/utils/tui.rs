@@ -0,0 +1,513 @@
+use crate::run::Fleet;
+use crate::traits::Observer;
+use crate::types::{Grid, Move};
+use crate::utils::svg::{colour_rgb, heat_rgb};
+use crate::utils::trajectory::action;
+use anyhow::Result;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table, Widget};
+use ratatui::{Frame, Terminal};
+use std::io::Stdout;
+use std::time::{Duration, Instant};
+
+/// The shortest and longest time a step is shown while the run is not paused.
+const DELAYS: (Duration, Duration) = (Duration::from_millis(1), Duration::from_secs(4));
+
+/// The largest number of cells per side of a block of the viewport.
+const MAX_ZOOM: usize = 64;
+
+/// The keys of the terminal UI, shown below the drones.
+const HELP: [&str; 6] = [
+    "space  pause / resume",
+    "n      single step",
+    "+ -    faster / slower",
+    "[ ]    zoom out / in",
+    "arrows move the cursor",
+    "q      quit",
+];
+
+/// What a drone last did.
+#[derive(Clone, Default)]
+struct DroneView {
+    /// The position of the drone, `None` while it is not active.
+    position: Option<(usize, usize)>,
+    /// The direction of its last move.
+    action: &'static str,
+    /// The number of cells of its last plan.
+    plan: usize,
+    /// The score the planner gave its last plan.
+    score: usize,
+    /// The value collected so far.
+    reward: usize,
+}
+
+/// The state of the terminal UI, independent of the terminal it is drawn on.
+struct View {
+    /// The number of time steps of the run.
+    time_steps: usize,
+    /// The last step that ended.
+    step: usize,
+    /// The grid at the end of the last step.
+    grid: Grid,
+    /// The last drone to visit every cell.
+    visited: Vec<Option<usize>>,
+    /// What every drone last did, indexed by drone id.
+    drones: Vec<DroneView>,
+    /// The inspected cell.
+    cursor: (usize, usize),
+    /// The block at the top left of the viewport.
+    origin: (usize, usize),
+    /// The number of cells per side of a block of the viewport.
+    zoom: usize,
+    /// How long a step is shown.
+    delay: Duration,
+    /// Whether the run waits for a key before the next step.
+    paused: bool,
+    /// Whether a single step was asked for while paused.
+    advance: bool,
+    /// Whether the run finished.
+    finished: bool,
+    /// Whether the user quit.
+    quit: bool,
+}
+
+impl View {
+    /// Creates the view of a run that has not started yet.
+    fn new(time_steps: usize) -> Self {
+        View {
+            time_steps,
+            step: 0,
+            grid: Grid::new(Vec::new()),
+            visited: Vec::new(),
+            drones: Vec::new(),
+            cursor: (0, 0),
+            origin: (0, 0),
+            zoom: 1,
+            delay: Duration::from_millis(200),
+            paused: false,
+            advance: false,
+            finished: false,
+            quit: false,
+        }
+    }
+
+    /// Returns the view of a drone, adding it if it is new.
+    fn drone(&mut self, drone: usize) -> &mut DroneView {
+        if drone >= self.drones.len() {
+            self.drones.resize(drone + 1, DroneView::default());
+        }
+        &mut self.drones[drone]
+    }
+
+    /// Marks a cell as last visited by a drone.
+    fn visit(&mut self, size: usize, (x, y): (usize, usize), drone: usize) {
+        if self.visited.len() != size * size {
+            self.visited = vec![None; size * size];
+        }
+        if x < size && y < size {
+            self.visited[x * size + y] = Some(drone);
+        }
+    }
+
+    /// Updates the view for a key press.
+    fn handle(&mut self, key: KeyCode) {
+        let size = self.grid.size.max(1);
+        let (x, y) = self.cursor;
+        let step = self.zoom;
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('n') => {
+                self.paused = true;
+                self.advance = true;
+            }
+            KeyCode::Char('+') => self.delay = (self.delay / 2).max(DELAYS.0),
+            KeyCode::Char('-') => self.delay = (self.delay * 2).min(DELAYS.1),
+            KeyCode::Char('[') => self.zoom = (self.zoom * 2).min(MAX_ZOOM),
+            KeyCode::Char(']') => self.zoom = (self.zoom / 2).max(1),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = (x.saturating_sub(step), y),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor = ((x + step).min(size - 1), y),
+            KeyCode::Left | KeyCode::Char('h') => self.cursor = (x, y.saturating_sub(step)),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor = (x, (y + step).min(size - 1)),
+            _ => {}
+        }
+    }
+
+    /// Scrolls the viewport so the cursor stays in sight.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The number of blocks per column of the viewport.
+    /// * `cols` - The number of blocks per row of the viewport.
+    fn scroll(&mut self, rows: usize, cols: usize) {
+        let blocks = self.grid.size.div_ceil(self.zoom);
+        let follow = |cursor: usize, origin: usize, visible: usize| {
+            let visible = visible.max(1);
+            let origin = origin.clamp(cursor.saturating_sub(visible - 1), cursor);
+            origin.min(blocks.saturating_sub(visible))
+        };
+        self.origin = (
+            follow(self.cursor.0 / self.zoom, self.origin.0, rows),
+            follow(self.cursor.1 / self.zoom, self.origin.1, cols),
+        );
+    }
+
+    /// Draws the view on a frame.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [left, sidebar] =
+            Layout::horizontal([Constraint::Min(20), Constraint::Length(46)]).areas(frame.area());
+        let [drones, cell, help] = Layout::vertical([
+            Constraint::Min(4),
+            Constraint::Length(6),
+            Constraint::Length(HELP.len() as u16 + 2),
+        ])
+        .areas(sidebar);
+
+        let state = if self.finished {
+            "finished, q to quit"
+        } else if self.paused {
+            "paused"
+        } else {
+            "running"
+        };
+        let block = Block::bordered().title(format!(
+            " Step {}/{} | {:.1} steps/s | zoom 1:{} | {} ",
+            self.step,
+            self.time_steps,
+            1. / self.delay.as_secs_f32(),
+            self.zoom,
+            state
+        ));
+        let viewport = block.inner(left);
+        frame.render_widget(block, left);
+        self.scroll(viewport.height as usize, viewport.width as usize / 2);
+        frame.render_widget(Viewport { view: self }, viewport);
+
+        let rows = self.drones.iter().enumerate().map(|(id, drone)| {
+            let (r, g, b) = colour_rgb(id);
+            let position = match drone.position {
+                Some((x, y)) => format!("{},{}", x, y),
+                None => "-".to_string(),
+            };
+            Row::new(vec![
+                id.to_string(),
+                position,
+                drone.action.to_string(),
+                drone.plan.to_string(),
+                drone.score.to_string(),
+                drone.reward.to_string(),
+            ])
+            .style(Style::default().fg(Color::Rgb(r, g, b)))
+        });
+        let widths = [
+            Constraint::Length(5),
+            Constraint::Length(9),
+            Constraint::Length(4),
+            Constraint::Length(4),
+            Constraint::Length(7),
+            Constraint::Length(8),
+        ];
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new(vec!["Drone", "At", "Move", "Plan", "Score", "Reward"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::bordered().title(" Drones "));
+        frame.render_widget(table, drones);
+
+        let (x, y) = self.cursor;
+        let mut lines = vec![format!("Cell {},{}", x, y)];
+        if x < self.grid.size && y < self.grid.size {
+            lines.push(format!("Value {}", self.grid.value_at(x, y)));
+            let here: Vec<String> = (self.drones.iter().enumerate())
+                .filter(|(_, drone)| drone.position == Some((x, y)))
+                .map(|(id, _)| id.to_string())
+                .collect();
+            lines.push(format!("Drones here: {}", here.join(", ")));
+            let last = self.visited.get(x * self.grid.size + y).copied().flatten();
+            lines.push(match last {
+                Some(drone) => format!("Last visited by drone {}", drone),
+                None => "Not visited".to_string(),
+            });
+        }
+        frame.render_widget(
+            Paragraph::new(lines.join("\n")).block(Block::bordered().title(" Cell ")),
+            cell,
+        );
+        frame.render_widget(
+            Paragraph::new(HELP.join("\n")).block(Block::bordered().title(" Keys ")),
+            help,
+        );
+    }
+}
+
+/// The grid as seen through the viewport, two characters per block.
+///
+/// Blocks are coloured by their mean value, active drones are drawn as their id on their
+/// colour and cells visited before as a dot in the colour of the last drone to visit them.
+struct Viewport<'a> {
+    /// The view to draw.
+    view: &'a View,
+}
+
+impl Widget for Viewport<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let view = self.view;
+        let (size, zoom) = (view.grid.size, view.zoom);
+        let max = view.grid.data.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let cursor = (view.cursor.0 / zoom, view.cursor.1 / zoom);
+
+        for row in 0..area.height as usize {
+            for col in 0..area.width as usize / 2 {
+                let block = (view.origin.0 + row, view.origin.1 + col);
+                let (x0, y0) = (block.0 * zoom, block.1 * zoom);
+                if x0 >= size || y0 >= size {
+                    continue;
+                }
+                let cells = (x0..(x0 + zoom).min(size))
+                    .flat_map(|x| (y0..(y0 + zoom).min(size)).map(move |y| (x, y)));
+                let (mut total, mut count, mut visitor) = (0, 0, None);
+                for (x, y) in cells {
+                    total += view.grid.value_at(x, y) as usize;
+                    count += 1;
+                    visitor = visitor.or(view.visited.get(x * size + y).copied().flatten());
+                }
+                let drone = (view.drones.iter().enumerate()).find_map(|(id, drone)| {
+                    let (x, y) = drone.position?;
+                    (x / zoom == block.0 && y / zoom == block.1).then_some(id)
+                });
+
+                let (r, g, b) = heat_rgb(total as f64 / count as f64 / max);
+                let mut style = Style::default().bg(Color::Rgb(r, g, b));
+                let symbol = match (drone, visitor) {
+                    (Some(id), _) => {
+                        let (r, g, b) = colour_rgb(id);
+                        style = style.bg(Color::Rgb(r, g, b)).fg(Color::White);
+                        format!("{:>2}", id % 100)
+                    }
+                    (None, Some(id)) => {
+                        let (r, g, b) = colour_rgb(id);
+                        style = style.fg(Color::Rgb(r, g, b));
+                        " ·".to_string()
+                    }
+                    (None, None) => "  ".to_string(),
+                };
+                if block == cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                let (x, y) = (area.x + col as u16 * 2, area.y + row as u16);
+                buf.set_string(x, y, symbol, style);
+            }
+        }
+    }
+}
+
+/// An observer that shows a run live in a full-screen terminal UI.
+///
+/// The grid is shown in a scrollable and zoomable viewport next to the position, last move,
+/// plan and reward of every drone and the value of the inspected cell. The run can be paused,
+/// run a single step at a time or sped up and slowed down. Once it finishes the UI stays open
+/// for inspection until it is quit, quitting earlier stops the run.
+pub struct Tui<B: Backend> {
+    /// The terminal the UI is drawn on.
+    terminal: Terminal<B>,
+    /// The state of the UI.
+    view: View,
+    /// Whether keys are read from the terminal and the terminal needs restoring.
+    interactive: bool,
+}
+
+impl Tui<CrosstermBackend<Stdout>> {
+    /// Switches the terminal to a full-screen UI, restored when the UI is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_steps` - The number of time steps of the run.
+    pub fn start(time_steps: usize) -> Result<Self> {
+        Ok(Tui {
+            terminal: ratatui::try_init()?,
+            view: View::new(time_steps),
+            interactive: true,
+        })
+    }
+}
+
+impl<B: Backend> Tui<B> {
+    /// Draws the UI on the given backend without reading keys, so a run is never held up.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to draw on.
+    /// * `time_steps` - The number of time steps of the run.
+    pub fn headless(backend: B, time_steps: usize) -> Result<Self> {
+        Ok(Tui {
+            terminal: Terminal::new(backend)?,
+            view: View::new(time_steps),
+            interactive: false,
+        })
+    }
+
+    /// Returns the backend the UI is drawn on.
+    pub fn backend(&self) -> &B {
+        self.terminal.backend()
+    }
+
+    /// Draws the UI.
+    fn draw(&mut self) -> Result<()> {
+        let view = &mut self.view;
+        self.terminal.draw(|frame| view.draw(frame))?;
+        Ok(())
+    }
+
+    /// Draws the UI and handles keys until the step has been shown long enough.
+    ///
+    /// # Returns
+    ///
+    /// A Result that is an error if the user quit.
+    fn wait(&mut self) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            self.draw()?;
+            if self.view.quit {
+                self.restore()?;
+                anyhow::bail!("Run stopped from the terminal UI");
+            }
+            if self.view.advance {
+                self.view.advance = false;
+                return Ok(());
+            }
+            let timeout = if self.view.paused {
+                Duration::from_millis(250)
+            } else {
+                match self.view.delay.checked_sub(start.elapsed()) {
+                    Some(timeout) => timeout,
+                    None => return Ok(()),
+                }
+            };
+            self.read(timeout)?;
+        }
+    }
+
+    /// Gives the terminal back, so logs and results are printed to the normal screen again.
+    fn restore(&mut self) -> Result<()> {
+        if self.interactive {
+            self.interactive = false;
+            ratatui::try_restore()?;
+        }
+        Ok(())
+    }
+
+    /// Handles the next key press, if there is one within the timeout.
+    fn read(&mut self, timeout: Duration) -> Result<()> {
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.view.handle(key.code);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B: Backend> Observer for Tui<B> {
+    fn on_move(&mut self, event: &Move) -> Result<()> {
+        self.view.visit(event.before.size, event.from, event.drone);
+        self.view.visit(event.before.size, event.to, event.drone);
+        let drone = self.view.drone(event.drone);
+        drone.action = action(event.from, event.to);
+        drone.plan = event.path.steps.len();
+        drone.score = event.path.total_cost;
+        drone.reward += event.reward;
+        Ok(())
+    }
+
+    fn on_step(&mut self, step: usize, grid: &Grid, fleet: &Fleet) -> Result<()> {
+        self.view.step = step;
+        self.view.grid.clone_from(grid);
+        for drone in 0..fleet.paths().len() {
+            self.view.drone(drone).position = None;
+        }
+        for (drone, state) in fleet.active() {
+            self.view.drone(drone).position = Some(state.position);
+        }
+        if self.interactive {
+            self.wait()
+        } else {
+            self.draw()
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.view.finished = true;
+        if self.interactive {
+            while !self.view.quit {
+                self.draw()?;
+                self.read(Duration::from_millis(250))?;
+            }
+            self.restore()?;
+        } else {
+            self.draw()?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Backend> Drop for Tui<B> {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Path;
+    use ratatui::backend::TestBackend;
+    use std::collections::VecDeque;
+
+    /// Tests the drones table, the inspected cell and the keys of a headless UI.
+    #[test]
+    fn test_tui_view() {
+        let before = Grid::new(vec![9, 2, 3, 4]);
+        let after = Grid::new(vec![0, 3, 3, 4]);
+        let path = Path {
+            steps: VecDeque::from([(0, 0), (1, 1)]),
+            total_cost: 7,
+        };
+        let mut tui = Tui::headless(TestBackend::new(80, 20), 5).unwrap();
+        tui.on_move(&Move {
+            step: 1,
+            drone: 0,
+            from: (0, 0),
+            to: (1, 1),
+            path: &path,
+            reward: 9,
+            before: &before,
+            after: &after,
+        })
+        .unwrap();
+        let mut fleet = Fleet::new(&[(0, 0)]);
+        fleet.update_drone(0, crate::types::DroneState::new((1, 1)));
+        tui.on_step(1, &after, &fleet).unwrap();
+
+        let text: String = (tui.backend().buffer().content.iter())
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(text.contains("Step 1/5"));
+        assert!(text.contains("1,1       SE   2    7       9"), "{}", text);
+        assert!(text.contains("Last visited by drone 0"));
+
+        tui.view.handle(KeyCode::Down);
+        tui.view.handle(KeyCode::Char('['));
+        tui.view.handle(KeyCode::Char('n'));
+        assert_eq!(tui.view.cursor, (1, 0));
+        assert_eq!(tui.view.zoom, 2);
+        assert!(tui.view.paused && tui.view.advance);
+        tui.finish().unwrap();
+    }
+}